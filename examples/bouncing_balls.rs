@@ -31,9 +31,10 @@ impl IndexInfo for RegionIndex {
     type Component = Transform;
     type Value = Region;
     type Storage = HashmapStorage<Self>;
+    type Data = ();
     const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
 
-    fn value(t: &Transform) -> Region {
+    fn value(t: &Transform, _: &()) -> Region {
         get_region(&t.translation.xy())
     }
 }
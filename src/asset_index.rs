@@ -0,0 +1,245 @@
+use crate::index::UniquenessError;
+use crate::unique_multimap::UniqueMultiMap;
+use bevy::asset::AssetEvents;
+use bevy::ecs::event::EventCursor;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+/// Implement this trait on your own types to specify how an [`AssetIndex`] should behave.
+///
+/// This is the counterpart of [`IndexInfo`][crate::index::IndexInfo] for [`Asset`]s stored in
+/// [`Assets`] rather than components.
+pub trait AssetIndexInfo: Sized + 'static {
+    /// The type of asset to be indexed.
+    type Asset: Asset;
+    /// The type of value to be used when looking up assets.
+    type Value: Send + Sync + Hash + Eq + Clone;
+
+    /// The function used by [`AssetIndex::lookup`] to determine the value of an asset.
+    ///
+    /// The values returned by this function are cached, so this should always return the same
+    /// value given equal assets.
+    fn value(asset: &Self::Asset) -> Self::Value;
+}
+
+/// Storage for an [`AssetIndex`], which maps values to the [`AssetId`]s of assets with that value.
+///
+/// The storage is kept up to date by reading [`AssetEvent`]s, so it is only created by
+/// [`AssetIndexPlugin`].
+#[derive(Resource)]
+pub struct AssetIndexStorage<I: AssetIndexInfo> {
+    map: UniqueMultiMap<I::Value, AssetId<I::Asset>>,
+    cursor: EventCursor<AssetEvent<I::Asset>>,
+    initialized: bool,
+}
+
+impl<I: AssetIndexInfo> Default for AssetIndexStorage<I> {
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+            cursor: Default::default(),
+            initialized: false,
+        }
+    }
+}
+
+impl<I: AssetIndexInfo> AssetIndexStorage<I> {
+    // Catches up on asset events since the last refresh. The first refresh indexes every asset
+    // from scratch, since events from before the index existed may have already been dropped.
+    fn refresh(&mut self, assets: &Assets<I::Asset>, events: &Events<AssetEvent<I::Asset>>) {
+        if !self.initialized {
+            for (id, asset) in assets.iter() {
+                self.map.insert(&I::value(asset), id);
+            }
+            self.cursor = events.get_cursor_current();
+            self.initialized = true;
+            return;
+        }
+
+        for event in self.cursor.read(events) {
+            match event {
+                AssetEvent::Added { id } | AssetEvent::Modified { id } => match assets.get(*id) {
+                    Some(asset) => {
+                        self.map.insert(&I::value(asset), *id);
+                    }
+                    None => {
+                        self.map.remove(id);
+                    }
+                },
+                AssetEvent::Removed { id } => {
+                    self.map.remove(id);
+                }
+                AssetEvent::Unused { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
+            }
+        }
+    }
+}
+
+type AssetsOf<'w, I> = Res<'w, Assets<<I as AssetIndexInfo>::Asset>>;
+type AssetEventsOf<'w, I> = Res<'w, Events<AssetEvent<<I as AssetIndexInfo>::Asset>>>;
+
+/// A [`SystemParam`] that allows you to look up [`Asset`]s that match a certain value.
+///
+/// Requires [`AssetIndexPlugin`] to be added to the app, and the asset type to be initialized
+/// with [`init_asset`][bevy::asset::AssetApp::init_asset].
+///
+/// The index is refreshed at the end of [`PostUpdate`], after new [`AssetEvent`]s have been sent,
+/// and whenever it is used, so it always reflects the events sent so far. Changes to assets are
+/// only seen after their events are sent.
+#[derive(SystemParam)]
+pub struct AssetIndex<'w, I: AssetIndexInfo> {
+    storage: ResMut<'w, AssetIndexStorage<I>>,
+    assets: AssetsOf<'w, I>,
+    events: AssetEventsOf<'w, I>,
+}
+
+impl<'w, I: AssetIndexInfo> AssetIndex<'w, I> {
+    /// Get the ids of all of the assets that evaluate to the given value using
+    /// [`I::value`][`AssetIndexInfo::value`].
+    pub fn lookup<'a>(
+        &'a mut self,
+        val: &'a I::Value,
+    ) -> impl Iterator<Item = AssetId<I::Asset>> + 'a {
+        self.refresh();
+        self.storage.map.get(val).copied()
+    }
+
+    /// Get the id of the single asset that evaluates to the given value using
+    /// [`I::value`][`AssetIndexInfo::value`].
+    ///
+    /// Returns an error if there is not exactly one asset with the value.
+    pub fn lookup_single(
+        &mut self,
+        val: &I::Value,
+    ) -> Result<AssetId<I::Asset>, UniquenessError<AssetId<I::Asset>>>
+    where
+        I::Value: Debug,
+    {
+        UniquenessError::single(val, self.lookup(val))
+    }
+
+    /// Get the value that an asset is currently indexed under, if any.
+    pub fn value_of(&mut self, id: impl Into<AssetId<I::Asset>>) -> Option<&I::Value> {
+        self.refresh();
+        self.storage.map.get_key(&id.into())
+    }
+
+    /// Update the index with any [`AssetEvent`]s that it hasn't seen yet.
+    pub fn refresh(&mut self) {
+        self.storage.refresh(&self.assets, &self.events);
+    }
+}
+
+/// A [`Plugin`] that sets up the storage for an [`AssetIndex`] and keeps it up to date.
+pub struct AssetIndexPlugin<I: AssetIndexInfo> {
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: AssetIndexInfo> Default for AssetIndexPlugin<I> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: AssetIndexInfo> Plugin for AssetIndexPlugin<I> {
+    fn build(&self, app: &mut App) {
+        // Asset events are cleared after two frames, so they need to be read every frame.
+        app.init_resource::<AssetIndexStorage<I>>().add_systems(
+            PostUpdate,
+            (|mut index: AssetIndex<I>| index.refresh()).after(AssetEvents),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::asset::AssetPlugin;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Asset, TypePath)]
+    struct ItemDef {
+        id: String,
+    }
+
+    impl AssetIndexInfo for ItemDef {
+        type Asset = ItemDef;
+        type Value = String;
+
+        fn value(asset: &ItemDef) -> String {
+            asset.id.clone()
+        }
+    }
+
+    fn item(id: &str) -> ItemDef {
+        ItemDef { id: id.to_string() }
+    }
+
+    fn lookup(app: &mut App, id: &str) -> Vec<AssetId<ItemDef>> {
+        let id = id.to_string();
+        app.world_mut()
+            .run_system_once(move |mut idx: AssetIndex<ItemDef>| idx.lookup(&id).collect())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_asset_index() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<ItemDef>();
+
+        // Assets added before the index exists are picked up too.
+        let sword = app
+            .world_mut()
+            .resource_mut::<Assets<ItemDef>>()
+            .add(item("sword"));
+        app.add_plugins(AssetIndexPlugin::<ItemDef>::default());
+        let shield = app
+            .world_mut()
+            .resource_mut::<Assets<ItemDef>>()
+            .add(item("shield"));
+        app.update();
+
+        assert_eq!(lookup(&mut app, "sword"), vec![sword.id()]);
+        assert_eq!(lookup(&mut app, "shield"), vec![shield.id()]);
+        assert_eq!(
+            app.world_mut()
+                .run_system_once(
+                    |mut idx: AssetIndex<ItemDef>| idx.lookup_single(&"axe".to_string())
+                )
+                .unwrap(),
+            Err(UniquenessError::NoEntities {
+                value: "\"axe\"".to_string()
+            })
+        );
+
+        app.world_mut()
+            .resource_mut::<Assets<ItemDef>>()
+            .get_mut(&shield)
+            .unwrap()
+            .id = "axe".to_string();
+        app.update();
+        assert!(lookup(&mut app, "shield").is_empty());
+        assert_eq!(lookup(&mut app, "axe"), vec![shield.id()]);
+
+        app.world_mut()
+            .resource_mut::<Assets<ItemDef>>()
+            .remove(&sword);
+        // Several frames pass without the index being used.
+        for _ in 0..3 {
+            app.update();
+        }
+        assert!(lookup(&mut app, "sword").is_empty());
+        assert_eq!(
+            app.world_mut()
+                .run_system_once(move |mut idx: AssetIndex<ItemDef>| idx.value_of(&shield).cloned())
+                .unwrap(),
+            Some("axe".to_string())
+        );
+    }
+}
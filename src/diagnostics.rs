@@ -0,0 +1,173 @@
+use crate::index::IndexInfo;
+use crate::storage::IndexStorage;
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use std::any::type_name;
+use std::marker::PhantomData;
+
+/// A [`Plugin`] that registers [`Diagnostic`]s for an index's size and refresh cost, which can be
+/// read from the [`DiagnosticsStore`][bevy::diagnostic::DiagnosticsStore] or logged with
+/// [`LogDiagnosticsPlugin`][bevy::diagnostic::LogDiagnosticsPlugin].
+///
+/// Measurements are taken from [`IndexStorage::take_stats`] during the [`Last`] schedule of each
+/// frame, so refresh statistics are totals for the whole frame. Storages that don't track a
+/// statistic won't record any measurements for it.
+pub struct IndexDiagnosticsPlugin<I: IndexInfo> {
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: IndexInfo> Default for IndexDiagnosticsPlugin<I> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo> IndexDiagnosticsPlugin<I> {
+    /// Time spent refreshing the index each frame, in milliseconds.
+    pub fn refresh_time() -> DiagnosticPath {
+        Self::path("refresh_time")
+    }
+
+    /// Number of entities whose values were recomputed each frame.
+    pub fn refreshed_entities() -> DiagnosticPath {
+        Self::path("refreshed_entities")
+    }
+
+    /// Number of entities in the index.
+    pub fn entities() -> DiagnosticPath {
+        Self::path("entities")
+    }
+
+    /// Number of distinct values in the index.
+    pub fn buckets() -> DiagnosticPath {
+        Self::path("buckets")
+    }
+
+    /// Number of removed entities waiting for the index's next refresh.
+    pub fn pending_removals() -> DiagnosticPath {
+        Self::path("pending_removals")
+    }
+
+    fn path(name: &str) -> DiagnosticPath {
+        DiagnosticPath::from_components(["index", type_name::<I>(), name])
+    }
+}
+
+impl<I: IndexInfo> Plugin for IndexDiagnosticsPlugin<I> {
+    fn build(&self, app: &mut App) {
+        crate::index::init_index::<I>(app.world_mut());
+        app.register_diagnostic(Diagnostic::new(Self::refresh_time()).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::refreshed_entities()))
+            .register_diagnostic(Diagnostic::new(Self::entities()))
+            .register_diagnostic(Diagnostic::new(Self::buckets()))
+            .register_diagnostic(Diagnostic::new(Self::pending_removals()));
+
+        let paths = [
+            Self::refresh_time(),
+            Self::refreshed_entities(),
+            Self::entities(),
+            Self::buckets(),
+            Self::pending_removals(),
+        ];
+        app.add_systems(
+            Last,
+            move |mut storage: ResMut<I::Storage>, mut diagnostics: Diagnostics| {
+                // Reading stats isn't a change to the index.
+                let stats = storage.bypass_change_detection().take_stats();
+                let [refresh_time, refreshed_entities, entities, buckets, pending_removals] =
+                    &paths;
+                let mut measure = |path: &DiagnosticPath, value: Option<f64>| {
+                    if let Some(value) = value {
+                        diagnostics.add_measurement(path, || value);
+                    }
+                };
+                measure(
+                    refresh_time,
+                    stats.refresh_time.map(|t| t.as_secs_f64() * 1000.),
+                );
+                measure(
+                    refreshed_entities,
+                    stats.refreshed_entities.map(|n| n as f64),
+                );
+                measure(entities, stats.entities.map(|n| n as f64));
+                measure(buckets, stats.buckets.map(|n| n as f64));
+                measure(pending_removals, stats.pending_removals.map(|n| n as f64));
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::Index;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::HashmapStorage;
+    use bevy::diagnostic::DiagnosticsStore;
+
+    #[derive(Component)]
+    struct Number(u32);
+
+    impl IndexInfo for Number {
+        type Component = Number;
+        type Value = u32;
+        type Storage = HashmapStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Number, _: &()) -> u32 {
+            c.0
+        }
+    }
+
+    fn measurement(app: &App, path: DiagnosticPath) -> Option<f64> {
+        app.world()
+            .resource::<DiagnosticsStore>()
+            .get_measurement(&path)
+            .map(|m| m.value)
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        type Plugin = IndexDiagnosticsPlugin<Number>;
+
+        let mut app = App::new();
+        app.add_plugins(Plugin::default())
+            .add_systems(Update, |_: Index<Number>| {});
+        app.world_mut().spawn(Number(1));
+        app.world_mut().spawn(Number(1));
+        let c = app.world_mut().spawn(Number(2)).id();
+        app.update();
+
+        assert_eq!(measurement(&app, Plugin::refreshed_entities()), Some(3.));
+        assert_eq!(measurement(&app, Plugin::entities()), Some(3.));
+        assert_eq!(measurement(&app, Plugin::buckets()), Some(2.));
+        assert_eq!(measurement(&app, Plugin::pending_removals()), Some(0.));
+        assert!(measurement(&app, Plugin::refresh_time()).is_some());
+
+        app.world_mut().despawn(c);
+        app.world_mut().run_schedule(Last);
+        assert_eq!(measurement(&app, Plugin::refreshed_entities()), Some(0.));
+        assert_eq!(measurement(&app, Plugin::pending_removals()), Some(1.));
+
+        app.update();
+        assert_eq!(measurement(&app, Plugin::entities()), Some(2.));
+        assert_eq!(measurement(&app, Plugin::buckets()), Some(1.));
+        assert_eq!(measurement(&app, Plugin::pending_removals()), Some(0.));
+    }
+
+    #[test]
+    fn test_diagnostics_without_index_system() {
+        type Plugin = IndexDiagnosticsPlugin<Number>;
+
+        let mut app = App::new();
+        app.add_plugins(Plugin::default());
+        app.world_mut().spawn(Number(1));
+        app.update();
+
+        // The index is never used, so it has no entities yet.
+        assert_eq!(measurement(&app, Plugin::entities()), Some(0.));
+    }
+}
@@ -0,0 +1,587 @@
+use crate::index::UniquenessError;
+use crate::refresh_policy::IndexRefreshPolicy;
+use crate::unique_multimap::UniqueMultiMap;
+use bevy::ecs::component::{ComponentId, Tick};
+use bevy::ecs::entity_disabling::Disabled;
+use bevy::ecs::system::{SystemChangeTick, SystemParam};
+use bevy::prelude::*;
+use bevy::ptr::Ptr;
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+
+/// Values that can be used as keys in a dynamic index.
+///
+/// This is implemented for all types that are `Hash + Eq + Clone + Debug`.
+pub trait DynamicKey: Any + Send + Sync {
+    #[doc(hidden)]
+    fn dyn_hash(&self, state: &mut dyn Hasher);
+    #[doc(hidden)]
+    fn dyn_eq(&self, other: &dyn DynamicKey) -> bool;
+    #[doc(hidden)]
+    fn dyn_clone(&self) -> Box<dyn DynamicKey>;
+    #[doc(hidden)]
+    fn dyn_debug(&self, f: &mut Formatter<'_>) -> std::fmt::Result;
+    #[doc(hidden)]
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any + Send + Sync + Hash + Eq + Clone + Debug> DynamicKey for T {
+    fn dyn_hash(&self, mut state: &mut dyn Hasher) {
+        // Include the type so that equal bytes of different types are unlikely to collide.
+        self.type_id().hash(&mut state);
+        self.hash(&mut state);
+    }
+
+    fn dyn_eq(&self, other: &dyn DynamicKey) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+
+    fn dyn_clone(&self) -> Box<dyn DynamicKey> {
+        Box::new(self.clone())
+    }
+
+    fn dyn_debug(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt(f)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A type-erased value stored in a dynamic index.
+///
+/// Two `DynamicValue`s are only equal if they wrap values of the same type that are equal.
+pub struct DynamicValue(Box<dyn DynamicKey>);
+
+impl DynamicValue {
+    /// Wrap a value so that it can be used with a dynamic index.
+    pub fn new<T: DynamicKey>(value: T) -> Self {
+        Self(Box::new(value))
+    }
+
+    /// Get a reference to the wrapped value if it is of type `T`.
+    pub fn downcast_ref<T: DynamicKey>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl Hash for DynamicValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.dyn_hash(state)
+    }
+}
+impl PartialEq for DynamicValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.dyn_eq(&*other.0)
+    }
+}
+impl Eq for DynamicValue {}
+impl Clone for DynamicValue {
+    fn clone(&self) -> Self {
+        Self(self.0.dyn_clone())
+    }
+}
+impl Debug for DynamicValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.dyn_debug(f)
+    }
+}
+
+/// Function used by a dynamic index to compute the value of a component.
+///
+/// The [`Ptr`] always points to a component of the type registered with the index's
+/// [`ComponentId`]. Returning `None` leaves the entity out of the index.
+pub type DynamicValueFn = Box<dyn Fn(Ptr<'_>) -> Option<DynamicValue> + Send + Sync>;
+
+/// Defines an index at runtime, without needing to implement [`IndexInfo`][crate::index::IndexInfo].
+///
+/// Call [`register`][DynamicIndexDescriptor::register] to create the index, and then use the returned
+/// [`DynamicIndexId`] with the [`DynamicIndex`] system param to look up entities.
+pub struct DynamicIndexDescriptor {
+    component: ComponentId,
+    value: DynamicValueFn,
+    refresh_policy: IndexRefreshPolicy,
+    include_disabled: bool,
+}
+
+impl DynamicIndexDescriptor {
+    /// Describe an index over the component with the given [`ComponentId`], using an untyped function
+    /// to compute the value of each component.
+    pub fn new(
+        component: ComponentId,
+        refresh_policy: IndexRefreshPolicy,
+        value: impl Fn(Ptr<'_>) -> Option<DynamicValue> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            component,
+            value: Box::new(value),
+            refresh_policy,
+            include_disabled: false,
+        }
+    }
+
+    /// Whether entities with the [`Disabled`] component should be included in the index.
+    ///
+    /// Like [`IndexInfo::INCLUDE_DISABLED`][crate::index::IndexInfo::INCLUDE_DISABLED], this
+    /// defaults to `false`, so disabled entities are left out of the index until they are re-enabled.
+    pub fn include_disabled(mut self, include_disabled: bool) -> Self {
+        self.include_disabled = include_disabled;
+        self
+    }
+
+    /// Describe an index over a component type that is known at compile time.
+    pub fn typed<C: Component, V: DynamicKey>(
+        world: &mut World,
+        refresh_policy: IndexRefreshPolicy,
+        value: impl Fn(&C) -> V + Send + Sync + 'static,
+    ) -> Self {
+        let component = world.register_component::<C>();
+        Self::new(component, refresh_policy, move |ptr| {
+            // SAFETY: Dynamic indexes only pass in pointers to the component they were created for.
+            let component = unsafe { ptr.deref::<C>() };
+            Some(DynamicValue::new(value(component)))
+        })
+    }
+
+    /// Create the index in the world and return the id that can be used to access it.
+    ///
+    /// Entities which already have the component are indexed immediately.
+    pub fn register(self, world: &mut World) -> DynamicIndexId {
+        let component = self.component;
+        let refresh_policy = self.refresh_policy;
+
+        let mut storage = DynamicIndexStorage {
+            component,
+            value: self.value,
+            refresh_policy,
+            include_disabled: self.include_disabled,
+            map: Default::default(),
+            last_refresh_tick: Tick::new(0),
+            removed_entities: Vec::new(),
+        };
+        let change_tick = world.change_tick();
+        storage.rebuild(world, change_tick);
+
+        let mut indexes = world.get_resource_or_init::<DynamicIndexes>();
+        let id = DynamicIndexId(indexes.storages.len());
+        indexes.storages.push(Mutex::new(storage));
+        let add_refresh_system = refresh_policy.is_each_frame() && !indexes.refresh_system_added;
+        if add_refresh_system {
+            indexes.refresh_system_added = true;
+        }
+
+        if refresh_policy.is_when_inserted() {
+            world.spawn(
+                Observer::new(move |trigger: Trigger<OnInsert>, world: &World| {
+                    world
+                        .resource::<DynamicIndexes>()
+                        .storage(id)
+                        .reindex(world, trigger.target());
+                })
+                .with_component(component),
+            );
+        }
+        world.spawn(
+            Observer::new(
+                move |trigger: Trigger<OnRemove>, indexes: Res<DynamicIndexes>| {
+                    let mut storage = indexes.storage(id);
+                    if storage.refresh_policy.is_when_inserted() {
+                        storage.map.remove(&trigger.target());
+                    } else {
+                        storage.removed_entities.push(trigger.target());
+                    }
+                },
+            )
+            .with_component(component),
+        );
+
+        if !self.include_disabled {
+            world.spawn(Observer::new(
+                move |trigger: Trigger<OnInsert, Disabled>, indexes: Res<DynamicIndexes>| {
+                    indexes.storage(id).map.remove(&trigger.target());
+                },
+            ));
+            world.spawn(Observer::new(
+                move |trigger: Trigger<OnRemove, Disabled>, mut commands: Commands| {
+                    let target = trigger.target();
+                    // `Disabled` is also removed when the entity is despawned, so wait until it is
+                    // actually gone to see whether the entity should be added back.
+                    commands.queue(move |world: &mut World| {
+                        world
+                            .resource::<DynamicIndexes>()
+                            .storage(id)
+                            .reindex(world, target);
+                    });
+                },
+            ));
+        }
+
+        if add_refresh_system {
+            world
+                .resource_mut::<Schedules>()
+                .add_systems(First, refresh_dynamic_indexes_system);
+        }
+
+        id
+    }
+}
+
+/// Identifies an index created with a [`DynamicIndexDescriptor`].
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DynamicIndexId(usize);
+
+/// [`Resource`] that holds the data for all dynamic indexes.
+#[derive(Resource, Default)]
+pub struct DynamicIndexes {
+    storages: Vec<Mutex<DynamicIndexStorage>>,
+    refresh_system_added: bool,
+}
+
+impl DynamicIndexes {
+    fn storage(&self, id: DynamicIndexId) -> MutexGuard<'_, DynamicIndexStorage> {
+        self.storages
+            .get(id.0)
+            .expect("Dynamic index is not registered in this world.")
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+struct DynamicIndexStorage {
+    component: ComponentId,
+    value: DynamicValueFn,
+    refresh_policy: IndexRefreshPolicy,
+    include_disabled: bool,
+    map: UniqueMultiMap<DynamicValue, Entity>,
+    last_refresh_tick: Tick,
+    removed_entities: Vec<Entity>,
+}
+
+impl DynamicIndexStorage {
+    fn refresh(&mut self, world: &World, this_run: Tick) {
+        if self.last_refresh_tick != this_run {
+            self.force_refresh(world, this_run);
+        }
+    }
+
+    fn force_refresh(&mut self, world: &World, this_run: Tick) {
+        for entity in self.removed_entities.drain(..) {
+            self.map.remove(&entity);
+        }
+        // Subtract 1 so that changes from the system where the index was updated are seen,
+        // like `HashmapStorage` does.
+        let last_run = Tick::new(self.last_refresh_tick.get().wrapping_sub(1));
+        let component = self.component;
+        self.refresh_entities(world, |entity| {
+            entity
+                .get_change_ticks_by_id(component)
+                .is_some_and(|ticks| ticks.is_changed(last_run, this_run))
+        });
+        self.last_refresh_tick = this_run;
+    }
+
+    fn rebuild(&mut self, world: &World, this_run: Tick) {
+        self.map = Default::default();
+        self.removed_entities.clear();
+        self.refresh_entities(world, |_| true);
+        self.last_refresh_tick = this_run;
+    }
+
+    fn refresh_entities(&mut self, world: &World, filter: impl Fn(&EntityRef) -> bool) {
+        let component = self.component;
+        let archetypes = world
+            .archetypes()
+            .iter()
+            .filter(|archetype| archetype.contains(component));
+        for archetype in archetypes {
+            for entity in archetype.entities() {
+                let entity = world.entity(entity.id());
+                if filter(&entity) {
+                    self.reindex_entity(entity);
+                }
+            }
+        }
+    }
+
+    fn reindex(&mut self, world: &World, entity: Entity) {
+        if let Ok(entity) = world.get_entity(entity) {
+            self.reindex_entity(entity);
+        }
+    }
+
+    fn reindex_entity(&mut self, entity: EntityRef) {
+        if !self.include_disabled && entity.contains::<Disabled>() {
+            self.map.remove(&entity.id());
+            return;
+        }
+        let value = entity.get_by_id(self.component).ok().and_then(&self.value);
+        match value {
+            Some(value) => {
+                self.map.insert(&value, entity.id());
+            }
+            None => {
+                self.map.remove(&entity.id());
+            }
+        }
+    }
+}
+
+/// A [`SystemParam`] that allows you to lookup entities in indexes created at runtime with a
+/// [`DynamicIndexDescriptor`].
+///
+/// Since the indexed components are not known ahead of time, this param has read-only access to
+/// the entire [`World`]. If you need to mutate components in the same system, you can combine
+/// them in a [`ParamSet`].
+///
+/// Dynamic indexes with the [`WhenRun`][IndexRefreshPolicy::WhenRun] policy are refreshed the first
+/// time they are used in each system, just like the [`WhenUsed`][IndexRefreshPolicy::WhenUsed] policy.
+#[derive(SystemParam)]
+pub struct DynamicIndex<'w> {
+    world: &'w World,
+    ticks: SystemChangeTick,
+}
+
+impl<'w> DynamicIndex<'w> {
+    /// Get all of the entities whose components evaluate to the given value in the given index.
+    pub fn lookup(&self, id: DynamicIndexId, val: &DynamicValue) -> impl Iterator<Item = Entity> {
+        let mut storage = self.storage(id);
+        if storage.refresh_policy.is_when_run() || storage.refresh_policy.is_when_used() {
+            storage.refresh(self.world, self.ticks.this_run());
+        }
+        storage
+            .map
+            .get(val)
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Get the single entity whose component evaluates to the given value in the given index.
+    ///
+    /// Returns an error if there is not exactly one `Entity` returned by the lookup.
+    pub fn lookup_single(
+        &self,
+        id: DynamicIndexId,
+        val: &DynamicValue,
+    ) -> Result<Entity, UniquenessError> {
+        UniquenessError::single(val, self.lookup(id, val))
+    }
+
+    /// Refresh the given index if it hasn't already been refreshed this [`Tick`].
+    pub fn refresh(&self, id: DynamicIndexId) {
+        self.storage(id).refresh(self.world, self.ticks.this_run());
+    }
+
+    /// Unconditionally refresh the given index.
+    pub fn force_refresh(&self, id: DynamicIndexId) {
+        self.storage(id)
+            .force_refresh(self.world, self.ticks.this_run());
+    }
+
+    fn storage(&self, id: DynamicIndexId) -> MutexGuard<'w, DynamicIndexStorage> {
+        self.world.resource::<DynamicIndexes>().storage(id)
+    }
+}
+
+/// A system that refreshes all dynamic indexes with the
+/// [`EachFrame`][IndexRefreshPolicy::EachFrame] refresh policy.
+///
+/// It is automatically added to the [`First`] schedule when the first such index is registered.
+pub fn refresh_dynamic_indexes_system(idx: DynamicIndex) {
+    let Some(indexes) = idx.world.get_resource::<DynamicIndexes>() else {
+        return;
+    };
+    for storage in &indexes.storages {
+        let mut storage = storage
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if storage.refresh_policy.is_each_frame() {
+            storage.refresh(idx.world, idx.ticks.this_run());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    struct Level(u32);
+
+    fn spawn_levels(mut commands: Commands) {
+        commands.spawn(Level(1));
+        commands.spawn(Level(1));
+        commands.spawn(Level(2));
+    }
+
+    fn register(policy: IndexRefreshPolicy) -> impl Fn(&mut World) {
+        move |world: &mut World| {
+            let id = DynamicIndexDescriptor::typed(world, policy, |level: &Level| level.0)
+                .register(world);
+            world.insert_resource(LevelIndex(id));
+        }
+    }
+
+    #[derive(Resource)]
+    struct LevelIndex(DynamicIndexId);
+
+    fn checker(level: u32, amount: usize) -> impl Fn(DynamicIndex, Res<LevelIndex>) {
+        move |idx: DynamicIndex, id: Res<LevelIndex>| {
+            let n = idx.lookup(id.0, &DynamicValue::new(level)).count();
+            assert_eq!(
+                n, amount,
+                "Index returned {} matches for {}, expected {}.",
+                n, level, amount,
+            );
+        }
+    }
+
+    fn level_up(mut levels: Query<&mut Level>) {
+        for mut level in &mut levels {
+            level.0 += 1;
+        }
+    }
+
+    fn test_policy(policy: IndexRefreshPolicy) {
+        let mut app = App::new();
+        app.add_systems(Startup, (spawn_levels, register(policy)).chain())
+            .add_systems(PreUpdate, checker(1, 2))
+            .add_systems(PreUpdate, checker(2, 1))
+            .add_systems(Update, level_up)
+            .add_systems(PostUpdate, checker(1, 0))
+            .add_systems(PostUpdate, checker(2, 2))
+            .add_systems(PostUpdate, checker(3, 1));
+        app.update();
+    }
+
+    #[test]
+    fn test_dynamic_index_when_run() {
+        test_policy(IndexRefreshPolicy::WhenRun);
+    }
+
+    #[test]
+    fn test_dynamic_index_when_used() {
+        test_policy(IndexRefreshPolicy::WhenUsed);
+    }
+
+    #[test]
+    fn test_dynamic_index_each_frame() {
+        let mut app = App::new();
+        app.add_systems(
+            Startup,
+            (spawn_levels, register(IndexRefreshPolicy::EachFrame)).chain(),
+        )
+        .add_systems(Update, level_up);
+        app.update();
+        app.add_systems(PreUpdate, checker(1, 0))
+            .add_systems(PreUpdate, checker(2, 2))
+            .add_systems(PreUpdate, checker(3, 1));
+        app.update();
+    }
+
+    #[test]
+    fn test_dynamic_index_when_inserted() {
+        let mut app = App::new();
+        app.add_systems(
+            Startup,
+            (register(IndexRefreshPolicy::WhenInserted), spawn_levels).chain(),
+        )
+        .add_systems(PreUpdate, checker(1, 2))
+        .add_systems(
+            Update,
+            |levels: Query<(Entity, &Level)>, mut commands: Commands| {
+                for (entity, level) in &levels {
+                    if level.0 == 1 {
+                        commands.entity(entity).insert(Level(5));
+                    } else {
+                        commands.entity(entity).despawn();
+                    }
+                }
+            },
+        )
+        .add_systems(PostUpdate, checker(1, 0))
+        .add_systems(PostUpdate, checker(2, 0))
+        .add_systems(PostUpdate, checker(5, 2));
+        app.update();
+    }
+
+    #[test]
+    fn test_dynamic_index_untyped() {
+        let mut world = World::new();
+        world.spawn(Level(7));
+        world.spawn(Level(8));
+        let component = world.register_component::<Level>();
+        let id = DynamicIndexDescriptor::new(component, IndexRefreshPolicy::Manual, |ptr| {
+            // SAFETY: The index is over `Level` components.
+            let level = unsafe { ptr.deref::<Level>() };
+            (level.0 % 2 == 0).then(|| DynamicValue::new("even"))
+        })
+        .register(&mut world);
+
+        let found = world
+            .run_system_once(move |idx: DynamicIndex| {
+                idx.lookup_single(id, &DynamicValue::new("even"))
+            })
+            .unwrap();
+        assert!(found.is_ok());
+        let missing = world
+            .run_system_once(move |idx: DynamicIndex| {
+                idx.lookup_single(id, &DynamicValue::new("odd"))
+            })
+            .unwrap();
+        assert_eq!(
+            missing,
+            Err(UniquenessError::NoEntities {
+                value: "\"odd\"".to_string()
+            })
+        );
+    }
+
+    fn disabled_test(policy: IndexRefreshPolicy, include_disabled: bool) {
+        let mut world = World::new();
+        let a = world.spawn(Level(1)).id();
+        world.spawn(Level(1));
+        let id = DynamicIndexDescriptor::typed(&mut world, policy, |level: &Level| level.0)
+            .include_disabled(include_disabled)
+            .register(&mut world);
+        let count = |world: &mut World, level: u32| {
+            world
+                .run_system_once(move |idx: DynamicIndex| {
+                    idx.refresh(id);
+                    idx.lookup(id, &DynamicValue::new(level)).count()
+                })
+                .unwrap()
+        };
+        let disabled_amount = if include_disabled { 2 } else { 1 };
+
+        world.entity_mut(a).insert(Disabled);
+        assert_eq!(count(&mut world, 1), disabled_amount);
+
+        // Changes made while disabled are seen once the entity is re-enabled.
+        world.entity_mut(a).insert(Level(2));
+        assert_eq!(count(&mut world, 2), disabled_amount - 1);
+        world.entity_mut(a).remove::<Disabled>();
+        assert_eq!(count(&mut world, 1), 1);
+        assert_eq!(count(&mut world, 2), 1);
+
+        // Despawning a disabled entity doesn't add it back.
+        world.entity_mut(a).insert(Disabled);
+        world.despawn(a);
+        assert_eq!(count(&mut world, 2), 0);
+    }
+
+    #[test]
+    fn test_dynamic_index_disabled_entities() {
+        disabled_test(IndexRefreshPolicy::Manual, false);
+        disabled_test(IndexRefreshPolicy::WhenInserted, false);
+    }
+
+    #[test]
+    fn test_dynamic_index_disabled_entities_included() {
+        disabled_test(IndexRefreshPolicy::Manual, true);
+    }
+}
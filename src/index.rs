@@ -1,5 +1,5 @@
 use crate::refresh_policy::{refresh_index_system, IndexRefreshPolicy};
-use crate::storage::IndexStorage;
+use crate::storage::{HashmapStorage, IndexStorage};
 use bevy::ecs::archetype::Archetype;
 use bevy::ecs::component::Tick;
 use bevy::ecs::system::{
@@ -8,10 +8,13 @@ use bevy::ecs::system::{
     StaticSystemParam,
     SystemMeta,
     SystemParam,
+    SystemParamItem,
 };
 use bevy::ecs::world::unsafe_world_cell::UnsafeWorldCell;
 use bevy::prelude::*;
+use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hash;
+use std::marker::PhantomData;
 
 /// Implement this trait on your own types to specify how an [`Index`] should behave.
 ///
@@ -22,36 +25,150 @@ pub trait IndexInfo: Sized + 'static {
     /// The type of component to be indexed.
     type Component: Component;
     /// The type of value to be used when looking up components.
-    type Value: Send + Sync + Hash + Eq + Clone;
+    ///
+    /// Storages may require more of this type, e.g. [`HashmapStorage`] requires it to be
+    /// [`Hash`] and [`Eq`].
+    type Value: Send + Sync + PartialEq + Clone;
     /// The type of storage to use for the index.
     type Storage: IndexStorage<Self>;
+    /// Extra read-only data that is passed to [`value`][`IndexInfo::value`], e.g. a
+    /// [`Res`] containing configuration. Use `()` if no extra data is needed.
+    ///
+    /// Storages rebuild the entire index when this data changes.
+    type Data: IndexData;
     /// Defines when the index should be automatically refreshed.
     const REFRESH_POLICY: IndexRefreshPolicy;
+    /// Whether entities with the [`Disabled`][bevy::ecs::entity_disabling::Disabled] component
+    /// are included in the index.
+    ///
+    /// By default, disabled entities are left out of the index, matching Bevy's default query
+    /// filters. They are removed from the index when they are disabled and added back when they
+    /// are re-enabled.
+    const INCLUDE_DISABLED: bool = false;
 
     /// The function used by [`Index::lookup`] to determine the value of a component.
     ///
     /// The values returned by this function are typically cached by the storage, so
-    /// this should always return the same value given equal [`Component`]s.
-    fn value(c: &Self::Component) -> Self::Value;
+    /// this should always return the same value given equal [`Component`]s and equal `data`.
+    fn value(c: &Self::Component, data: &SystemParamItem<Self::Data>) -> Self::Value;
 }
 
+/// A read-only [`SystemParam`] that can be used as [`IndexInfo::Data`].
+///
+/// In addition to being fetched alongside the index, implementors must be able to report whether
+/// they have changed, so that values computed from the old data can be thrown away.
+///
+/// This is implemented for `()`, [`Res`], `Option<Res>`, and tuples of up to 8 other
+/// implementors.
+pub trait IndexData: ReadOnlySystemParam + 'static {
+    /// Returns `true` if this data has changed after `last_run` (and not after `this_run`).
+    fn is_changed(data: &SystemParamItem<Self>, last_run: Tick, this_run: Tick) -> bool;
+}
+
+impl<T: Resource> IndexData for Res<'static, T> {
+    fn is_changed(data: &SystemParamItem<Self>, last_run: Tick, this_run: Tick) -> bool {
+        data.last_changed().is_newer_than(last_run, this_run)
+    }
+}
+
+impl<T: Resource> IndexData for Option<Res<'static, T>> {
+    fn is_changed(data: &SystemParamItem<Self>, last_run: Tick, this_run: Tick) -> bool {
+        data.as_ref()
+            .is_some_and(|res| res.last_changed().is_newer_than(last_run, this_run))
+    }
+}
+
+macro_rules! impl_index_data_tuple {
+    ($($param:ident),*) => {
+        impl<$($param: IndexData),*> IndexData for ($($param,)*) {
+            #[allow(unused_variables, non_snake_case)]
+            fn is_changed(data: &SystemParamItem<Self>, last_run: Tick, this_run: Tick) -> bool {
+                let ($($param,)*) = data;
+                false $(|| $param::is_changed($param, last_run, this_run))*
+            }
+        }
+    };
+}
+
+impl_index_data_tuple!();
+impl_index_data_tuple!(P0);
+impl_index_data_tuple!(P0, P1);
+impl_index_data_tuple!(P0, P1, P2);
+impl_index_data_tuple!(P0, P1, P2, P3);
+impl_index_data_tuple!(P0, P1, P2, P3, P4);
+impl_index_data_tuple!(P0, P1, P2, P3, P4, P5);
+impl_index_data_tuple!(P0, P1, P2, P3, P4, P5, P6);
+impl_index_data_tuple!(P0, P1, P2, P3, P4, P5, P6, P7);
+
 /// A [`SystemParam`] that allows you to lookup [`Component`]s that match a certain value.
 pub struct Index<'w, 's, I: IndexInfo + 'static> {
-    storage: ResMut<'w, I::Storage>,
-    refresh_data:
+    pub(crate) storage: ResMut<'w, I::Storage>,
+    pub(crate) refresh_data:
         StaticSystemParam<'w, 's, <I::Storage as IndexStorage<I>>::RefreshData<'static, 'static>>,
 }
 
 /// Error returned by [`Index::lookup_single`] if there is not exactly one Entity with the
 /// requested value.
-#[derive(Eq, PartialEq, Debug, Copy, Clone)]
-pub enum UniquenessError {
+///
+/// The error includes the requested value's [`Debug`] output and any entities that were found, and
+/// implements [`Error`][std::error::Error], so it can be returned from fallible systems with `?`.
+///
+/// Other lookups that expect a single result, e.g. [`AssetIndex`][crate::asset_index::AssetIndex]
+/// with the `asset` feature, use the same error with the type of result they return instead of
+/// [`Entity`].
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum UniquenessError<T = Entity> {
     /// There were no entities with the requested value.
-    NoEntities,
+    NoEntities {
+        /// The [`Debug`] output of the requested value.
+        value: String,
+    },
     /// There was more than one entity with the requested value.
-    MultipleEntities,
+    MultipleEntities {
+        /// The [`Debug`] output of the requested value.
+        value: String,
+        /// All of the entities that were found.
+        found: Vec<T>,
+    },
+}
+
+impl<T> UniquenessError<T> {
+    /// Returns the single item from the results of a lookup for `value`, or an error if there
+    /// isn't exactly one.
+    pub(crate) fn single(
+        value: &(impl Debug + ?Sized),
+        mut found: impl Iterator<Item = T>,
+    ) -> Result<T, Self> {
+        match (found.next(), found.next()) {
+            (None, _) => Err(Self::NoEntities {
+                value: format!("{value:?}"),
+            }),
+            (Some(item), None) => Ok(item),
+            (Some(first), Some(second)) => Err(Self::MultipleEntities {
+                value: format!("{value:?}"),
+                found: [first, second].into_iter().chain(found).collect(),
+            }),
+        }
+    }
 }
 
+impl<T: Debug> Display for UniquenessError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoEntities { value } => {
+                write!(f, "Expected 1 entity with value {value} in index, found 0.")
+            }
+            Self::MultipleEntities { value, found } => write!(
+                f,
+                "Expected 1 entity with value {value} in index, found {}: {found:?}.",
+                found.len()
+            ),
+        }
+    }
+}
+
+impl<T: Debug> std::error::Error for UniquenessError<T> {}
+
 #[doc(hidden)]
 /// Thanks Jon https://youtu.be/CWiz_RtA1Hw?t=815
 pub trait Captures<U> {}
@@ -68,9 +185,8 @@ impl<'w, 's, I: IndexInfo> Index<'w, 's, I> {
         &'self_ mut self,
         val: &'i I::Value,
     ) -> impl Iterator<Item = Entity> + Captures<(&'w (), &'s (), &'self_ (), &'i ())> {
-        if I::REFRESH_POLICY.is_when_used() {
-            self.refresh();
-        }
+        index_span!("index_lookup", I);
+        self.prepare_storage();
         self.storage.lookup(val, &mut self.refresh_data)
     }
 
@@ -80,33 +196,25 @@ impl<'w, 's, I: IndexInfo> Index<'w, 's, I> {
     /// Refreshes the index if it has not yet been refreshed in this system and the index's
     /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][`IndexRefreshPolicy::WhenUsed`].
     ///
-    /// Returns an error if there is not exactly one `Entity` returned by the lookup.
-    /// See [`Index::single`] for the panicking version.
-    pub fn lookup_single(&mut self, val: &I::Value) -> Result<Entity, UniquenessError> {
-        let mut it = self.lookup(val);
-        match (it.next(), it.next()) {
-            (None, _) => Err(UniquenessError::NoEntities),
-            (Some(e), None) => Ok(e),
-            (Some(_), Some(_)) => Err(UniquenessError::MultipleEntities),
-        }
+    /// Returns an error if there is not exactly one `Entity` returned by the lookup, which can be
+    /// propagated with `?` in systems that return a [`Result`].
+    pub fn lookup_single(&mut self, val: &I::Value) -> Result<Entity, UniquenessError>
+    where
+        I::Value: Debug,
+    {
+        UniquenessError::single(val, self.lookup(val))
     }
 
     /// Get the single entity with relevant components that evaluate to the given value
     /// using [`I::value`][`IndexInfo::value`].
     ///
-    /// Refreshes the index if it has not yet been refreshed in this system and the index's
-    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][`IndexRefreshPolicy::WhenUsed`].
-    ///
-    /// Panics if there is not exactly one `Entity` returned by the lookup.
-    /// See [`Index::lookup_single`] for the version that returns a result instead.
-    pub fn single(&mut self, val: &I::Value) -> Entity {
-        match self.lookup_single(val) {
-            Err(UniquenessError::NoEntities) => panic!("Expected 1 entity in index, found 0."),
-            Ok(e) => e,
-            Err(UniquenessError::MultipleEntities) => {
-                panic!("Expected 1 entity in index, found multiple.")
-            }
-        }
+    /// This is the same as [`Index::lookup_single`], and like [`Query::single`], returns an error
+    /// rather than panicking if there is not exactly one `Entity` returned by the lookup.
+    pub fn single(&mut self, val: &I::Value) -> Result<Entity, UniquenessError>
+    where
+        I::Value: Debug,
+    {
+        self.lookup_single(val)
     }
 
     /// Refresh the underlying [`IndexStorage`] for this index if it hasn't already been refreshed
@@ -125,6 +233,83 @@ impl<'w, 's, I: IndexInfo> Index<'w, 's, I> {
     pub fn force_refresh(&mut self) {
         self.storage.force_refresh(&mut self.refresh_data)
     }
+
+    // Brings the storage up to date before it is read, refreshing it if the index is refreshed
+    // when used. Methods that read from the storage directly must call this first.
+    pub(crate) fn prepare_storage(&mut self) {
+        if I::REFRESH_POLICY.is_when_used() {
+            self.refresh();
+        }
+        self.storage.prepare_lookup(&mut self.refresh_data);
+    }
+}
+
+impl<'w, 's, I: IndexInfo<Storage = HashmapStorage<I>>> Index<'w, 's, I>
+where
+    I::Value: Hash + Eq,
+{
+    /// Get the value that an entity's component was indexed under, if any.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][`IndexRefreshPolicy::WhenUsed`].
+    pub fn value_of(&mut self, entity: Entity) -> Option<&I::Value> {
+        self.prepare_storage();
+        self.storage.value_of(&entity)
+    }
+}
+
+// Sets up the storage resource, observers, and systems needed by an index if they don't exist yet.
+pub(crate) fn init_index<I: IndexInfo>(world: &mut World) {
+    if !world.contains_resource::<I::Storage>() {
+        world.init_resource::<I::Storage>();
+        if I::REFRESH_POLICY.is_each_frame() {
+            world
+                .resource_mut::<Schedules>()
+                .get_mut(First)
+                .expect("Can't find `First` schedule.")
+                .add_systems(refresh_index_system::<I>);
+        }
+
+        if let Some(obs) = I::Storage::insertion_observer() {
+            world.spawn(obs);
+            // Catch up on missed data
+            world.run_system_once(refresh_index_system::<I>).unwrap();
+        }
+
+        if let Some(obs) = I::Storage::removal_observer() {
+            world.spawn(obs);
+        }
+
+        for obs in I::Storage::disabling_observers() {
+            world.spawn(obs);
+        }
+
+        #[cfg(feature = "scene")]
+        world.spawn(crate::scene::scene_ready_observer::<I>());
+    }
+}
+
+/// A [`Plugin`] that eagerly initializes the storage for an index.
+///
+/// Indexes are otherwise initialized the first time a system using them is initialized. Adding
+/// this plugin ensures that indexes using observers don't need to catch up on components that were
+/// inserted before then.
+pub struct IndexPlugin<I: IndexInfo> {
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: IndexInfo> Default for IndexPlugin<I> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo> Plugin for IndexPlugin<I> {
+    fn build(&self, app: &mut App) {
+        init_index::<I>(app.world_mut());
+    }
 }
 
 #[doc(hidden)]
@@ -143,26 +328,7 @@ where
     type State = IndexFetchState<'static, 'static, I>;
     type Item<'_w, '_s> = Index<'_w, '_s, I>;
     fn init_state(world: &mut World, system_meta: &mut SystemMeta) -> Self::State {
-        if !world.contains_resource::<I::Storage>() {
-            world.init_resource::<I::Storage>();
-            if I::REFRESH_POLICY.is_each_frame() {
-                world
-                    .resource_mut::<Schedules>()
-                    .get_mut(First)
-                    .expect("Can't find `First` schedule.")
-                    .add_systems(refresh_index_system::<I>);
-            }
-
-            if let Some(obs) = I::Storage::insertion_observer() {
-                world.spawn(obs);
-                // Catch up on missed data
-                world.run_system_once(refresh_index_system::<I>).unwrap();
-            }
-
-            if let Some(obs) = I::Storage::removal_observer() {
-                world.spawn(obs);
-            }
-        }
+        init_index::<I>(world);
         IndexFetchState {
             storage_state: <ResMut<'w, I::Storage> as SystemParam>::init_state(world, system_meta),
             refresh_data_state: <StaticSystemParam<
@@ -248,6 +414,7 @@ where
 
 #[cfg(test)]
 mod test {
+    use crate::index::UniquenessError;
     use crate::prelude::*;
     use bevy::prelude::*;
 
@@ -259,9 +426,10 @@ mod test {
         type Component = Self;
         type Value = Self;
         type Storage = HashmapStorage<Self>;
+        type Data = ();
         const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
 
-        fn value(c: &Self::Component) -> Self::Value {
+        fn value(c: &Self::Component, _: &()) -> Self::Value {
             c.clone()
         }
     }
@@ -294,6 +462,7 @@ mod test {
         }
     }
 
+    #[allow(clippy::type_complexity)]
     fn adder_some(
         n: usize,
         condition: usize,
@@ -330,27 +499,58 @@ mod test {
             .add_systems(Startup, add_some_numbers)
             .add_systems(Update, |mut idx: Index<Number>| {
                 let num = Number(20);
-                assert_eq!(vec![idx.single(&num)], idx.lookup(&num).collect::<Vec<_>>());
+                assert_eq!(
+                    vec![idx.single(&num).unwrap()],
+                    idx.lookup(&num).collect::<Vec<_>>()
+                );
             })
             .run();
     }
     #[test]
-    #[should_panic]
     fn test_index_lookup_single_but_zero() {
         App::new()
             .add_systems(Startup, add_some_numbers)
             .add_systems(Update, |mut idx: Index<Number>| {
-                idx.single(&Number(55));
+                let err = idx.single(&Number(55)).unwrap_err();
+                assert_eq!(
+                    err,
+                    UniquenessError::NoEntities {
+                        value: "Number(55)".to_string()
+                    }
+                );
+                assert_eq!(
+                    err.to_string(),
+                    "Expected 1 entity with value Number(55) in index, found 0."
+                );
             })
             .run();
     }
     #[test]
-    #[should_panic]
     fn test_index_lookup_single_but_many() {
         App::new()
             .add_systems(Startup, add_some_numbers)
             .add_systems(Update, |mut idx: Index<Number>| {
-                idx.single(&Number(10));
+                let mut expected: Vec<_> = idx.lookup(&Number(10)).collect();
+                let Err(UniquenessError::MultipleEntities { value, mut found }) =
+                    idx.lookup_single(&Number(10))
+                else {
+                    panic!("Expected multiple entities.");
+                };
+                assert_eq!(value, "Number(10)");
+                expected.sort();
+                found.sort();
+                assert_eq!(found, expected);
+            })
+            .run();
+    }
+    #[test]
+    #[should_panic(expected = "Expected 1 entity with value Number(55) in index, found 0.")]
+    fn test_index_lookup_single_in_fallible_system() {
+        App::new()
+            .add_systems(Startup, add_some_numbers)
+            .add_systems(Update, |mut idx: Index<Number>| -> Result {
+                idx.lookup_single(&Number(55))?;
+                Ok(())
             })
             .run();
     }
@@ -498,8 +698,9 @@ mod test {
             type Component = Number;
             type Value = Number;
             type Storage = HashmapStorage<Self>;
+            type Data = ();
             const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenInserted;
-            fn value(c: &Self::Component) -> Self::Value {
+            fn value(c: &Self::Component, _: &()) -> Self::Value {
                 c.clone()
             }
         }
@@ -525,4 +726,169 @@ mod test {
 
         app.update();
     }
+
+    #[derive(Resource)]
+    struct Divisor(usize);
+
+    fn data_index_test<I: IndexInfo<Value = Number>>() {
+        fn set_divisor(n: usize) -> impl Fn(ResMut<Divisor>) {
+            move |mut divisor: ResMut<Divisor>| divisor.0 = n
+        }
+
+        let mut app = App::new();
+        app.insert_resource(Divisor(10))
+            .add_systems(Startup, add_some_numbers)
+            .add_systems(PreUpdate, checker::<I>(1, 2))
+            .add_systems(PreUpdate, checker::<I>(2, 1))
+            .add_systems(Update, set_divisor(20))
+            .add_systems(PostUpdate, checker::<I>(0, 2))
+            .add_systems(PostUpdate, checker::<I>(1, 2))
+            .add_systems(PostUpdate, checker::<I>(2, 0));
+        app.update();
+    }
+
+    #[test]
+    fn test_data_change_rebuild() {
+        struct DividedIndex;
+        impl IndexInfo for DividedIndex {
+            type Component = Number;
+            type Value = Number;
+            type Storage = HashmapStorage<Self>;
+            type Data = Res<'static, Divisor>;
+            const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+            fn value(c: &Self::Component, divisor: &Res<Divisor>) -> Self::Value {
+                Number(c.0 / divisor.0)
+            }
+        }
+
+        data_index_test::<DividedIndex>();
+    }
+
+    #[test]
+    fn test_data_change_rebuild_with_observers() {
+        struct DividedObserverIndex;
+        impl IndexInfo for DividedObserverIndex {
+            type Component = Number;
+            type Value = Number;
+            type Storage = HashmapStorage<Self>;
+            type Data = (Res<'static, Divisor>,);
+            const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenInserted;
+            fn value(c: &Self::Component, (divisor,): &(Res<Divisor>,)) -> Self::Value {
+                Number(c.0 / divisor.0)
+            }
+        }
+
+        data_index_test::<DividedObserverIndex>();
+    }
+
+    #[test]
+    fn test_data_no_storage() {
+        struct DividedNoStorageIndex;
+        impl IndexInfo for DividedNoStorageIndex {
+            type Component = Number;
+            type Value = Number;
+            type Storage = NoStorage<Self>;
+            type Data = Res<'static, Divisor>;
+            const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::Manual;
+            fn value(c: &Self::Component, divisor: &Res<Divisor>) -> Self::Value {
+                Number(c.0 / divisor.0)
+            }
+        }
+
+        data_index_test::<DividedNoStorageIndex>();
+    }
+
+    fn disabled_index_test<I: IndexInfo<Value = Number>>(disabled_amount: usize) {
+        use bevy::ecs::entity_disabling::Disabled;
+        use bevy::ecs::system::RunSystemOnce;
+
+        let mut app = App::new();
+        let a = app.world_mut().spawn(Number(10)).id();
+        app.world_mut().spawn(Number(10));
+        app.world_mut()
+            .run_system_once(checker::<I>(10, 2))
+            .unwrap();
+
+        app.world_mut().entity_mut(a).insert(Disabled);
+        app.world_mut()
+            .run_system_once(checker::<I>(10, disabled_amount))
+            .unwrap();
+
+        // Changes made while disabled are seen once the entity is re-enabled.
+        app.world_mut().entity_mut(a).insert(Number(20));
+        app.world_mut()
+            .run_system_once(checker::<I>(20, disabled_amount - 1))
+            .unwrap();
+        app.world_mut().entity_mut(a).remove::<Disabled>();
+        app.world_mut()
+            .run_system_once(checker::<I>(10, 1))
+            .unwrap();
+        app.world_mut()
+            .run_system_once(checker::<I>(20, 1))
+            .unwrap();
+
+        // Despawning a disabled entity doesn't add it back.
+        app.world_mut().entity_mut(a).insert(Disabled);
+        app.world_mut().despawn(a);
+        app.world_mut()
+            .run_system_once(checker::<I>(20, 0))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_disabled_entities() {
+        disabled_index_test::<Number>(1);
+    }
+
+    #[test]
+    fn test_disabled_entities_with_observers() {
+        struct ObserverIndex;
+        impl IndexInfo for ObserverIndex {
+            type Component = Number;
+            type Value = Number;
+            type Storage = HashmapStorage<Self>;
+            type Data = ();
+            const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenInserted;
+            fn value(c: &Self::Component, _: &()) -> Self::Value {
+                c.clone()
+            }
+        }
+
+        disabled_index_test::<ObserverIndex>(1);
+    }
+
+    #[test]
+    fn test_disabled_entities_included() {
+        struct IncludeDisabledIndex;
+        impl IndexInfo for IncludeDisabledIndex {
+            type Component = Number;
+            type Value = Number;
+            type Storage = HashmapStorage<Self>;
+            type Data = ();
+            const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+            const INCLUDE_DISABLED: bool = true;
+            fn value(c: &Self::Component, _: &()) -> Self::Value {
+                c.clone()
+            }
+        }
+
+        disabled_index_test::<IncludeDisabledIndex>(2);
+    }
+
+    #[test]
+    fn test_disabled_entities_no_storage() {
+        struct NoStorageIndex;
+        impl IndexInfo for NoStorageIndex {
+            type Component = Number;
+            type Value = Number;
+            type Storage = NoStorage<Self>;
+            type Data = ();
+            const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::Manual;
+            fn value(c: &Self::Component, _: &()) -> Self::Value {
+                c.clone()
+            }
+        }
+
+        disabled_index_test::<NoStorageIndex>(1);
+    }
 }
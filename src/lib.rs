@@ -10,6 +10,15 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 #![allow(clippy::needless_lifetimes)]
 
+// Enters a span named after an index for the rest of the enclosing block when the `trace` feature
+// is enabled, so that index work shows up in profilers.
+macro_rules! index_span {
+    ($name:literal, $index:ty) => {
+        #[cfg(feature = "trace")]
+        let _span = bevy::log::info_span!($name, index = std::any::type_name::<$index>()).entered();
+    };
+}
+
 /// Main index logic.
 pub mod index;
 
@@ -19,11 +28,70 @@ pub mod storage;
 /// Policy definitions and utilities for automatically refreshing indexes.
 pub mod refresh_policy;
 
+/// Indexes that are defined at runtime.
+pub mod dynamic;
+
+/// Dynamic indexes that use reflection to compute their values.
+#[cfg(feature = "reflect")]
+pub mod reflect_index;
+
+/// Ready-made index for looking up entities by [`Name`](bevy::ecs::name::Name).
+pub mod name;
+
+/// Ready-made indexes for entity relationships.
+pub mod relationship;
+
+/// Diagnostics for the size and refresh cost of indexes.
+pub mod diagnostics;
+
+/// Debug tools for checking that indexes agree with the world.
+#[cfg(feature = "validation")]
+pub mod validation;
+
+/// Indexes for looking up [`Asset`](bevy::asset::Asset)s by value.
+#[cfg(feature = "asset")]
+pub mod asset_index;
+
+/// Bevy Remote Protocol methods for querying indexes.
+#[cfg(feature = "remote")]
+pub mod remote;
+
+#[cfg(feature = "scene")]
+mod scene;
+
 mod unique_multimap;
 
 /// Commonly used types.
 pub mod prelude {
-    pub use crate::index::{Index, IndexInfo};
+    #[cfg(feature = "asset")]
+    pub use crate::asset_index::{AssetIndex, AssetIndexInfo, AssetIndexPlugin};
+    pub use crate::index::{Index, IndexInfo, IndexPlugin};
+    pub use crate::name::{NameIndex, NameIndexPlugin};
     pub use crate::refresh_policy::*;
-    pub use crate::storage::{HashmapStorage, IndexStorage, NoStorage};
+    pub use crate::relationship::RelationshipIndex;
+    pub use crate::storage::{
+        Aggregate,
+        AggregateStorage,
+        AggregatingStorage,
+        BkTreeStorage,
+        BvhBounds,
+        BvhStorage,
+        CompositeKey,
+        CompositeStorage,
+        DenseKey,
+        DenseStorage,
+        EntityBitSet,
+        FlagStorage,
+        HashmapStorage,
+        IndexStorage,
+        IntervalBounds,
+        IntervalStorage,
+        KeyPrefix,
+        Levenshtein,
+        Metric,
+        MultiStorage,
+        NoStorage,
+        SortedStorage,
+        ViewStorage,
+    };
 }
@@ -0,0 +1,141 @@
+use crate::index::{Index, IndexInfo, IndexPlugin, UniquenessError};
+use crate::refresh_policy::IndexRefreshPolicy;
+use crate::storage::HashmapStorage;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+/// [`IndexInfo`] that indexes entities by their exact [`Name`].
+///
+/// Usually used through the [`NameIndex`] system param.
+pub struct ExactName;
+
+impl IndexInfo for ExactName {
+    type Component = Name;
+    type Value = String;
+    type Storage = HashmapStorage<Self>;
+    type Data = ();
+    const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenUsed;
+
+    fn value(c: &Name, _: &()) -> String {
+        c.as_str().to_owned()
+    }
+}
+
+/// [`IndexInfo`] that indexes entities by their lowercased [`Name`].
+///
+/// Usually used through the [`NameIndex`] system param.
+pub struct LowercaseName;
+
+impl IndexInfo for LowercaseName {
+    type Component = Name;
+    type Value = String;
+    type Storage = HashmapStorage<Self>;
+    type Data = ();
+    const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenUsed;
+
+    fn value(c: &Name, _: &()) -> String {
+        c.as_str().to_lowercase()
+    }
+}
+
+/// A [`Plugin`] that sets up the indexes used by [`NameIndex`].
+pub struct NameIndexPlugin;
+
+impl Plugin for NameIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            IndexPlugin::<ExactName>::default(),
+            IndexPlugin::<LowercaseName>::default(),
+        ));
+    }
+}
+
+/// A [`SystemParam`] that allows you to look up entities by their [`Name`].
+///
+/// Changes to names are picked up the first time the index is used in each system.
+#[derive(SystemParam)]
+pub struct NameIndex<'w, 's> {
+    exact: Index<'w, 's, ExactName>,
+    lowercase: Index<'w, 's, LowercaseName>,
+}
+
+impl<'w, 's> NameIndex<'w, 's> {
+    /// Get all of the entities with exactly the given name.
+    pub fn lookup(&mut self, name: &str) -> impl Iterator<Item = Entity> + '_ {
+        self.exact.refresh();
+        self.exact.storage.get(name)
+    }
+
+    /// Get the single entity with exactly the given name.
+    ///
+    /// Returns an error if there is not exactly one `Entity` with the name.
+    pub fn lookup_single(&mut self, name: &str) -> Result<Entity, UniquenessError> {
+        UniquenessError::single(name, self.lookup(name))
+    }
+
+    /// Get all of the entities whose names start with the given prefix.
+    ///
+    /// This checks every distinct name in the index.
+    pub fn lookup_prefix<'a>(&'a mut self, prefix: &'a str) -> impl Iterator<Item = Entity> + 'a {
+        self.exact.refresh();
+        self.exact
+            .storage
+            .iter()
+            .filter(move |(name, _)| name.starts_with(prefix))
+            .flat_map(|(_, entities)| entities)
+    }
+
+    /// Get all of the entities with the given name, ignoring case.
+    pub fn lookup_case_insensitive(&mut self, name: &str) -> impl Iterator<Item = Entity> + '_ {
+        self.lowercase.refresh();
+        self.lowercase.storage.get(&name.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn count(world: &mut World, f: fn(&mut NameIndex) -> usize) -> usize {
+        world
+            .run_system_once(move |mut idx: NameIndex| f(&mut idx))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_name_index() {
+        let mut app = App::new();
+        app.add_plugins(NameIndexPlugin);
+        let world = app.world_mut();
+        world.spawn(Name::new("Goblin"));
+        world.spawn(Name::new("goblin"));
+        world.spawn(Name::new("Goblin King"));
+        let orc = world.spawn(Name::new("Orc")).id();
+
+        assert_eq!(count(world, |idx| idx.lookup("Goblin").count()), 1);
+        assert_eq!(count(world, |idx| idx.lookup("GOBLIN").count()), 0);
+        assert_eq!(
+            count(world, |idx| idx.lookup_case_insensitive("GOBLIN").count()),
+            2
+        );
+        assert_eq!(count(world, |idx| idx.lookup_prefix("Gob").count()), 2);
+        assert_eq!(count(world, |idx| idx.lookup_prefix("").count()), 4);
+        assert_eq!(
+            world
+                .run_system_once(|mut idx: NameIndex| idx.lookup_single("Orc"))
+                .unwrap(),
+            Ok(orc)
+        );
+
+        world.get_mut::<Name>(orc).unwrap().set("Goblin Shaman");
+        assert_eq!(count(world, |idx| idx.lookup("Orc").count()), 0);
+        assert_eq!(count(world, |idx| idx.lookup_prefix("Goblin").count()), 3);
+        assert_eq!(
+            count(world, |idx| idx
+                .lookup_case_insensitive("goblin shaman")
+                .count()),
+            1
+        );
+    }
+}
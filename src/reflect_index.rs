@@ -0,0 +1,291 @@
+use crate::dynamic::{DynamicIndexDescriptor, DynamicValue};
+use crate::refresh_policy::IndexRefreshPolicy;
+use bevy::prelude::*;
+use bevy::reflect::{GetPath, ParsedPath, PartialReflect, ReflectFromPtr};
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+
+/// A reflected value that can be used as a key in a dynamic index.
+///
+/// Values are hashed with [`PartialReflect::reflect_hash`] and compared with
+/// [`PartialReflect::reflect_partial_eq`], so their types must register `Hash` and `PartialEq`
+/// reflection data, e.g. with `#[reflect(Hash, PartialEq)]`.
+pub struct ReflectValue {
+    value: Box<dyn PartialReflect>,
+    hash: u64,
+}
+
+impl ReflectValue {
+    /// Wrap a reflected value.
+    ///
+    /// Returns `None` if the value cannot be hashed.
+    pub fn new(value: Box<dyn PartialReflect>) -> Option<Self> {
+        let hash = value.reflect_hash()?;
+        Some(Self { value, hash })
+    }
+
+    /// Get the wrapped value.
+    pub fn value(&self) -> &dyn PartialReflect {
+        &*self.value
+    }
+}
+
+impl From<ReflectValue> for DynamicValue {
+    fn from(value: ReflectValue) -> Self {
+        DynamicValue::new(value)
+    }
+}
+
+impl Hash for ReflectValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state)
+    }
+}
+impl PartialEq for ReflectValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+            && self
+                .value
+                .reflect_partial_eq(&*other.value)
+                .unwrap_or(false)
+    }
+}
+impl Eq for ReflectValue {}
+impl Clone for ReflectValue {
+    fn clone(&self) -> Self {
+        Self {
+            value: clone_reflect(&*self.value),
+            hash: self.hash,
+        }
+    }
+}
+impl Debug for ReflectValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+// Prefer a concrete clone, but fall back to a dynamic representation for types that can't be cloned.
+fn clone_reflect(value: &dyn PartialReflect) -> Box<dyn PartialReflect> {
+    value
+        .reflect_clone()
+        .map(PartialReflect::into_partial_reflect)
+        .unwrap_or_else(|_| value.to_dynamic())
+}
+
+/// Error returned by [`DynamicIndexDescriptor::reflect_path`] if the index cannot be created.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum ReflectIndexError {
+    /// No type with the given type path is registered in the [`AppTypeRegistry`].
+    UnknownType(String),
+    /// The type is missing [`ReflectComponent`] data, e.g. from `#[reflect(Component)]`.
+    NotAComponent(String),
+    /// The type is missing [`ReflectFromPtr`] data.
+    MissingFromPtr(String),
+    /// The field path could not be parsed.
+    InvalidPath(String),
+}
+
+impl Display for ReflectIndexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownType(ty) => write!(f, "No type with the path `{ty}` is registered."),
+            Self::NotAComponent(ty) => write!(
+                f,
+                "`{ty}` is not a reflected component. Add `#[reflect(Component)]` to it."
+            ),
+            Self::MissingFromPtr(ty) => write!(f, "`{ty}` has no registered `ReflectFromPtr`."),
+            Self::InvalidPath(path) => write!(f, "`{path}` is not a valid field path."),
+        }
+    }
+}
+
+impl std::error::Error for ReflectIndexError {}
+
+impl DynamicIndexDescriptor {
+    /// Describe an index over a reflected component, using the field at `field_path` as the value.
+    ///
+    /// `component` can be either the full or short type path of the component, e.g. `"Stats"`,
+    /// and the component must be registered in the [`AppTypeRegistry`] with `#[reflect(Component)]`.
+    /// `field_path` uses the syntax of [`GetPath`], e.g. `"level"` or `"faction.0"`.
+    ///
+    /// Values can be looked up by wrapping them in a [`ReflectValue`]. Entities whose components
+    /// don't have the field, or whose field value can't be hashed, are not indexed.
+    pub fn reflect_path(
+        world: &mut World,
+        component: &str,
+        field_path: &str,
+        refresh_policy: IndexRefreshPolicy,
+    ) -> Result<Self, ReflectIndexError> {
+        let path = ParsedPath::parse(field_path)
+            .map_err(|_| ReflectIndexError::InvalidPath(field_path.to_string()))?;
+
+        let registry = world.resource::<AppTypeRegistry>().clone();
+        let registry = registry.read();
+        let registration = registry
+            .get_with_type_path(component)
+            .or_else(|| registry.get_with_short_type_path(component))
+            .ok_or_else(|| ReflectIndexError::UnknownType(component.to_string()))?;
+        let reflect_component = registration
+            .data::<ReflectComponent>()
+            .ok_or_else(|| ReflectIndexError::NotAComponent(component.to_string()))?;
+        let from_ptr = registration
+            .data::<ReflectFromPtr>()
+            .ok_or_else(|| ReflectIndexError::MissingFromPtr(component.to_string()))?
+            .clone();
+        let component_id = reflect_component.register_component(world);
+
+        Ok(Self::new(component_id, refresh_policy, move |ptr| {
+            // SAFETY: Dynamic indexes only pass in pointers to the component they were created for,
+            // and the `ReflectFromPtr` came from the same type's registration.
+            let component = unsafe { from_ptr.as_reflect(ptr) };
+            let field = component.reflect_path(&path).ok()?;
+            ReflectValue::new(clone_reflect(field)).map(DynamicValue::from)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dynamic::{DynamicIndex, DynamicIndexId};
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Reflect, Clone, Copy, Hash, PartialEq, Eq, Debug)]
+    #[reflect(Hash, PartialEq)]
+    enum Faction {
+        Orcs,
+        Elves,
+    }
+
+    #[derive(Component, Reflect)]
+    #[reflect(Component)]
+    struct Enemy {
+        faction: Faction,
+    }
+
+    #[derive(Component, Reflect)]
+    #[reflect(Component)]
+    struct Stats {
+        level: u32,
+    }
+
+    fn setup_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<AppTypeRegistry>();
+        {
+            let registry = world.resource::<AppTypeRegistry>();
+            let mut registry = registry.write();
+            registry.register::<Enemy>();
+            registry.register::<Stats>();
+        }
+        world.spawn((
+            Enemy {
+                faction: Faction::Orcs,
+            },
+            Stats { level: 3 },
+        ));
+        world.spawn((
+            Enemy {
+                faction: Faction::Orcs,
+            },
+            Stats { level: 5 },
+        ));
+        world.spawn((
+            Enemy {
+                faction: Faction::Elves,
+            },
+            Stats { level: 5 },
+        ));
+        world
+    }
+
+    fn count(world: &mut World, id: DynamicIndexId, value: impl PartialReflect) -> usize {
+        let value = ReflectValue::new(Box::new(value)).unwrap().into();
+        world
+            .run_system_once(move |idx: DynamicIndex| idx.lookup(id, &value).count())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_reflect_path_index() {
+        let mut world = setup_world();
+        let factions = DynamicIndexDescriptor::reflect_path(
+            &mut world,
+            "Enemy",
+            "faction",
+            IndexRefreshPolicy::WhenRun,
+        )
+        .unwrap()
+        .register(&mut world);
+        let levels = DynamicIndexDescriptor::reflect_path(
+            &mut world,
+            Stats::type_path(),
+            "level",
+            IndexRefreshPolicy::WhenRun,
+        )
+        .unwrap()
+        .register(&mut world);
+
+        assert_eq!(count(&mut world, factions, Faction::Orcs), 2);
+        assert_eq!(count(&mut world, factions, Faction::Elves), 1);
+        assert_eq!(count(&mut world, levels, 5u32), 2);
+        assert_eq!(count(&mut world, levels, 4u32), 0);
+
+        world
+            .run_system_once(|mut stats: Query<&mut Stats>| {
+                for mut stats in &mut stats {
+                    stats.level += 1;
+                }
+            })
+            .unwrap();
+        assert_eq!(count(&mut world, levels, 5u32), 0);
+        assert_eq!(count(&mut world, levels, 6u32), 2);
+    }
+
+    #[test]
+    fn test_reflect_path_errors() {
+        let mut world = setup_world();
+        let policy = IndexRefreshPolicy::WhenRun;
+        assert_eq!(
+            DynamicIndexDescriptor::reflect_path(&mut world, "Missing", "level", policy).err(),
+            Some(ReflectIndexError::UnknownType("Missing".to_string()))
+        );
+        assert_eq!(
+            DynamicIndexDescriptor::reflect_path(&mut world, "Stats", "level[", policy).err(),
+            Some(ReflectIndexError::InvalidPath("level[".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_reflect_path_in_fallible_system() {
+        let mut world = setup_world();
+        let register = |component: &str| {
+            let component = component.to_string();
+            move |world: &mut World| -> Result {
+                DynamicIndexDescriptor::reflect_path(
+                    world,
+                    &component,
+                    "level",
+                    IndexRefreshPolicy::WhenRun,
+                )?
+                .register(world);
+                Ok(())
+            }
+        };
+        assert!(world.run_system_once(register("Stats")).unwrap().is_ok());
+
+        let error = world
+            .run_system_once(register("Missing"))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ReflectIndexError>(),
+            Some(&ReflectIndexError::UnknownType("Missing".to_string()))
+        );
+        assert_eq!(
+            ReflectIndexError::UnknownType("Missing".to_string()).to_string(),
+            "No type with the path `Missing` is registered."
+        );
+    }
+}
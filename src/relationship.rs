@@ -0,0 +1,114 @@
+use crate::index::{Captures, Index, IndexInfo};
+use crate::refresh_policy::IndexRefreshPolicy;
+use crate::storage::HashmapStorage;
+use bevy::ecs::relationship::Relationship;
+use bevy::prelude::*;
+use std::marker::PhantomData;
+
+/// [`IndexInfo`] that indexes the sources of a [`Relationship`] such as [`ChildOf`] by their target.
+///
+/// This uses the [`WhenInserted`][IndexRefreshPolicy::WhenInserted] refresh policy, since Bevy
+/// expects relationships to be changed by re-inserting them rather than mutating them in place.
+/// Entries are cleaned up when a relationship is removed, including when its target is
+/// despawned and Bevy removes the relationship from (or despawns) the source entities.
+pub struct RelationshipIndex<R: Relationship> {
+    phantom: PhantomData<fn() -> R>,
+}
+
+impl<R: Relationship> IndexInfo for RelationshipIndex<R> {
+    type Component = R;
+    type Value = Entity;
+    type Storage = HashmapStorage<Self>;
+    type Data = ();
+    const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenInserted;
+
+    fn value(c: &R, _: &()) -> Entity {
+        c.get()
+    }
+}
+
+impl<'w, 's, R: Relationship> Index<'w, 's, RelationshipIndex<R>> {
+    /// Get all of the entities whose relationship targets the given entity.
+    pub fn sources<'i, 'self_>(
+        &'self_ mut self,
+        target: &'i Entity,
+    ) -> impl Iterator<Item = Entity> + Captures<(&'w (), &'s (), &'self_ (), &'i ())> {
+        self.lookup(target)
+    }
+
+    /// Get the entity that the given entity's relationship targets, if it has one.
+    pub fn target(&mut self, source: Entity) -> Option<Entity> {
+        self.value_of(source).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    #[relationship(relationship_target = LikedBy)]
+    struct Likes(Entity);
+
+    #[derive(Component)]
+    #[relationship_target(relationship = Likes)]
+    struct LikedBy(Vec<Entity>);
+
+    fn sources<R: Relationship>(world: &mut World, target: Entity) -> usize {
+        world
+            .run_system_once(move |mut idx: Index<RelationshipIndex<R>>| {
+                idx.sources(&target).count()
+            })
+            .unwrap()
+    }
+
+    fn target<R: Relationship>(world: &mut World, source: Entity) -> Option<Entity> {
+        world
+            .run_system_once(move |mut idx: Index<RelationshipIndex<R>>| idx.target(source))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_child_of_index() {
+        let mut world = World::new();
+        let parent = world.spawn_empty().id();
+        let other_parent = world.spawn_empty().id();
+        let child_a = world.spawn(ChildOf(parent)).id();
+        let child_b = world.spawn(ChildOf(parent)).id();
+
+        assert_eq!(sources::<ChildOf>(&mut world, parent), 2);
+        assert_eq!(target::<ChildOf>(&mut world, child_a), Some(parent));
+
+        world.entity_mut(child_b).insert(ChildOf(other_parent));
+        assert_eq!(sources::<ChildOf>(&mut world, parent), 1);
+        assert_eq!(sources::<ChildOf>(&mut world, other_parent), 1);
+        assert_eq!(target::<ChildOf>(&mut world, child_b), Some(other_parent));
+
+        // Children are despawned along with their parent.
+        world.despawn(parent);
+        assert_eq!(sources::<ChildOf>(&mut world, parent), 0);
+        assert_eq!(target::<ChildOf>(&mut world, child_a), None);
+        assert_eq!(sources::<ChildOf>(&mut world, other_parent), 1);
+    }
+
+    #[test]
+    fn test_custom_relationship_index() {
+        let mut world = World::new();
+        let idol = world.spawn_empty().id();
+        let fan_a = world.spawn(Likes(idol)).id();
+        let fan_b = world.spawn(Likes(idol)).id();
+
+        assert_eq!(sources::<Likes>(&mut world, idol), 2);
+
+        world.entity_mut(fan_a).remove::<Likes>();
+        assert_eq!(sources::<Likes>(&mut world, idol), 1);
+        assert_eq!(target::<Likes>(&mut world, fan_a), None);
+
+        // Sources are kept, but their relationship is removed.
+        world.despawn(idol);
+        assert!(world.get_entity(fan_b).is_ok());
+        assert_eq!(sources::<Likes>(&mut world, idol), 0);
+        assert_eq!(target::<Likes>(&mut world, fan_b), None);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::index::{Index, IndexInfo};
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::reflect::serde::TypedReflectDeserializer;
+use bevy::reflect::{GetTypeRegistration, TypePath};
+use bevy::remote::{error_codes, BrpError, BrpResult, RemoteMethodSystemId, RemoteMethods};
+use serde::de::DeserializeSeed;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::any::TypeId;
+use std::marker::PhantomData;
+
+/// The method path for a `bevy_mod_index/lookup` request.
+pub const BRP_LOOKUP_METHOD: &str = "bevy_mod_index/lookup";
+
+/// The method path for a `bevy_mod_index/list` request.
+pub const BRP_LIST_METHOD: &str = "bevy_mod_index/list";
+
+/// `bevy_mod_index/lookup`: Looks up the entities with a given value in an index.
+///
+/// The response is a list of the matching entities.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BrpIndexLookupParams {
+    /// The full or short type path of the index's [`IndexInfo`] type, as listed by
+    /// `bevy_mod_index/list`.
+    pub index: String,
+    /// The value to look up, in the same format that `bevy/get` uses for the value's type.
+    pub value: Value,
+}
+
+type RemoteLookupFn = fn(&mut World, Value) -> BrpResult<Vec<Entity>>;
+
+struct RemoteIndex {
+    type_path: &'static str,
+    short_type_path: &'static str,
+    lookup: RemoteLookupFn,
+}
+
+/// The indexes that can be queried with the Bevy Remote Protocol, added by [`RemoteIndexPlugin`].
+#[derive(Resource, Default)]
+pub struct RemoteIndexes(Vec<RemoteIndex>);
+
+impl RemoteIndexes {
+    /// Get the type paths of all of the indexes that can be queried.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.0.iter().map(|index| index.type_path)
+    }
+
+    fn get(&self, name: &str) -> Option<&RemoteIndex> {
+        self.0
+            .iter()
+            .find(|index| index.type_path == name)
+            .or_else(|| self.0.iter().find(|index| index.short_type_path == name))
+    }
+}
+
+/// A [`Plugin`] that allows an index to be queried with the `bevy_mod_index/lookup` and
+/// `bevy_mod_index/list` Bevy Remote Protocol methods.
+///
+/// The methods are added to the [`RemotePlugin`][bevy::remote::RemotePlugin]'s methods once the
+/// app is built, so the `RemotePlugin` must be added as well, in any order. Values are read from
+/// JSON using reflection, so the index's values must be reflectable.
+pub struct RemoteIndexPlugin<I: IndexInfo> {
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: IndexInfo> Default for RemoteIndexPlugin<I> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo + TypePath> Plugin for RemoteIndexPlugin<I>
+where
+    I::Value: FromReflect + TypePath + GetTypeRegistration,
+{
+    fn build(&self, app: &mut App) {
+        app.register_type::<I::Value>()
+            .init_resource::<RemoteIndexes>()
+            .world_mut()
+            .resource_mut::<RemoteIndexes>()
+            .0
+            .push(RemoteIndex {
+                type_path: I::type_path(),
+                short_type_path: I::short_type_path(),
+                lookup: remote_lookup::<I>,
+            });
+    }
+
+    fn finish(&self, app: &mut App) {
+        let world = app.world_mut();
+        let Some(methods) = world.get_resource::<RemoteMethods>() else {
+            return;
+        };
+        // Only the first index's plugin needs to add the methods.
+        if methods.get(BRP_LOOKUP_METHOD).is_some() {
+            return;
+        }
+        let lookup = world.register_system(process_index_lookup_request);
+        let list = world.register_system(process_index_list_request);
+        let mut methods = world.resource_mut::<RemoteMethods>();
+        methods.insert(BRP_LOOKUP_METHOD, RemoteMethodSystemId::Instant(lookup));
+        methods.insert(BRP_LIST_METHOD, RemoteMethodSystemId::Instant(list));
+    }
+}
+
+fn remote_lookup<I: IndexInfo>(world: &mut World, value: Value) -> BrpResult<Vec<Entity>>
+where
+    I::Value: FromReflect,
+{
+    let value = {
+        let registry = world.resource::<AppTypeRegistry>().read();
+        let registration = registry.get(TypeId::of::<I::Value>()).ok_or_else(|| {
+            BrpError::internal(format!(
+                "`{}` is not registered",
+                std::any::type_name::<I::Value>()
+            ))
+        })?;
+        let reflected = TypedReflectDeserializer::new(registration, &registry)
+            .deserialize(&value)
+            .map_err(invalid_params)?;
+        I::Value::from_reflect(&*reflected).ok_or_else(|| {
+            invalid_params(format!(
+                "Could not convert value to `{}`",
+                std::any::type_name::<I::Value>()
+            ))
+        })?
+    };
+    world
+        .run_system_once(move |mut index: Index<I>| index.lookup(&value).collect())
+        .map_err(BrpError::internal)
+}
+
+/// Handles a `bevy_mod_index/lookup` request coming from a client.
+pub fn process_index_lookup_request(In(params): In<Option<Value>>, world: &mut World) -> BrpResult {
+    let BrpIndexLookupParams { index, value } = match params {
+        Some(params) => serde_json::from_value(params).map_err(invalid_params)?,
+        None => return Err(invalid_params("Params not provided")),
+    };
+    let lookup = world
+        .get_resource::<RemoteIndexes>()
+        .and_then(|indexes| indexes.get(&index))
+        .map(|index| index.lookup)
+        .ok_or_else(|| invalid_params(format!("Unknown index `{index}`")))?;
+
+    serde_json::to_value(lookup(world, value)?).map_err(BrpError::internal)
+}
+
+/// Handles a `bevy_mod_index/list` request coming from a client.
+///
+/// The response is a list of the type paths of every index that can be queried.
+pub fn process_index_list_request(In(_params): In<Option<Value>>, world: &World) -> BrpResult {
+    let names: Vec<_> = world
+        .get_resource::<RemoteIndexes>()
+        .map(|indexes| indexes.names().collect())
+        .unwrap_or_default();
+    serde_json::to_value(names).map_err(BrpError::internal)
+}
+
+fn invalid_params(error: impl ToString) -> BrpError {
+    BrpError {
+        code: error_codes::INVALID_PARAMS,
+        message: error.to_string(),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::HashmapStorage;
+    use bevy::remote::RemotePlugin;
+    use serde_json::json;
+
+    #[derive(Component)]
+    struct Team(u32);
+
+    #[derive(TypePath)]
+    struct TeamIndex;
+
+    impl IndexInfo for TeamIndex {
+        type Component = Team;
+        type Value = u32;
+        type Storage = HashmapStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Team, _: &()) -> u32 {
+            c.0
+        }
+    }
+
+    fn request(app: &mut App, method: &str, params: Option<Value>) -> BrpResult {
+        let world = app.world_mut();
+        let Some(RemoteMethodSystemId::Instant(id)) =
+            world.resource::<RemoteMethods>().get(method).copied()
+        else {
+            panic!("Method `{method}` not found");
+        };
+        world.run_system_with(id, params).unwrap()
+    }
+
+    #[test]
+    fn test_remote_methods() {
+        let mut app = App::new();
+        app.add_plugins((
+            RemoteIndexPlugin::<TeamIndex>::default(),
+            RemotePlugin::default(),
+        ));
+        app.finish();
+        let a = app.world_mut().spawn(Team(1)).id();
+        let b = app.world_mut().spawn(Team(1)).id();
+        app.world_mut().spawn(Team(2));
+
+        assert_eq!(
+            request(&mut app, BRP_LIST_METHOD, None).unwrap(),
+            json!([TeamIndex::type_path()])
+        );
+
+        let mut entities: Vec<Entity> = serde_json::from_value(
+            request(
+                &mut app,
+                BRP_LOOKUP_METHOD,
+                Some(json!({ "index": "TeamIndex", "value": 1 })),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        entities.sort();
+        assert_eq!(entities, vec![a, b]);
+
+        let error = request(
+            &mut app,
+            BRP_LOOKUP_METHOD,
+            Some(json!({ "index": "Missing", "value": 1 })),
+        )
+        .unwrap_err();
+        assert_eq!(error.code, error_codes::INVALID_PARAMS);
+
+        let error = request(
+            &mut app,
+            BRP_LOOKUP_METHOD,
+            Some(json!({ "index": "TeamIndex", "value": "one" })),
+        )
+        .unwrap_err();
+        assert_eq!(error.code, error_codes::INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::index::{Index, IndexInfo};
+use bevy::prelude::*;
+use bevy::scene::SceneInstanceReady;
+
+// Components spawned from scenes arrive in bulk, and indexes that aren't refreshed automatically
+// (or that miss changes made after insertion) would otherwise be out of date until their next
+// refresh, so every index is refreshed as soon as a scene instance is ready.
+pub(crate) fn scene_ready_observer<I: IndexInfo>() -> Observer {
+    Observer::new(|_: Trigger<SceneInstanceReady>, mut index: Index<I>| {
+        index.force_refresh();
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::HashmapStorage;
+    use bevy::asset::AssetPlugin;
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::scene::{DynamicScene, ScenePlugin, SceneSpawner};
+
+    #[derive(Component, Reflect)]
+    #[reflect(Component)]
+    struct Team(u32);
+
+    struct ObservedTeam;
+    impl IndexInfo for ObservedTeam {
+        type Component = Team;
+        type Value = u32;
+        type Storage = HashmapStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenInserted;
+
+        fn value(c: &Team, _: &()) -> u32 {
+            c.0
+        }
+    }
+
+    struct ManualTeam;
+    impl IndexInfo for ManualTeam {
+        type Component = Team;
+        type Value = u32;
+        type Storage = HashmapStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::Manual;
+
+        fn value(c: &Team, _: &()) -> u32 {
+            c.0
+        }
+    }
+
+    fn team_sizes(app: &mut App, team: u32) -> (usize, usize) {
+        app.world_mut()
+            .run_system_once(
+                move |mut observed: Index<ObservedTeam>, mut manual: Index<ManualTeam>| {
+                    (observed.lookup(&team).count(), manual.lookup(&team).count())
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_scene_refresh() {
+        let mut app = App::new();
+        app.add_plugins((AssetPlugin::default(), ScenePlugin))
+            .register_type::<Team>();
+        let registry = app.world().resource::<AppTypeRegistry>().clone();
+
+        let mut scene_world = World::new();
+        scene_world.insert_resource(registry);
+        scene_world.spawn(Team(1));
+        scene_world.spawn(Team(1));
+        scene_world.spawn(Team(2));
+        let scene = app
+            .world_mut()
+            .resource_mut::<Assets<DynamicScene>>()
+            .add(DynamicScene::from_world(&scene_world));
+
+        assert_eq!(team_sizes(&mut app, 1), (0, 0));
+
+        let instance = app
+            .world_mut()
+            .resource_mut::<SceneSpawner>()
+            .spawn_dynamic(scene.clone());
+        app.update();
+        assert_eq!(team_sizes(&mut app, 1), (2, 2));
+        assert_eq!(team_sizes(&mut app, 2), (1, 1));
+
+        // Despawned entities are removed by the observer index right away, and by the manual index
+        // the next time a scene is ready.
+        app.world_mut()
+            .resource_mut::<SceneSpawner>()
+            .despawn_instance(instance);
+        app.update();
+        assert_eq!(team_sizes(&mut app, 1), (0, 2));
+
+        app.world_mut()
+            .resource_mut::<SceneSpawner>()
+            .spawn_dynamic(scene);
+        app.update();
+        assert_eq!(team_sizes(&mut app, 1), (2, 2));
+        assert_eq!(team_sizes(&mut app, 2), (1, 1));
+    }
+}
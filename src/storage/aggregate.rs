@@ -0,0 +1,443 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// A fold over the components of all entities with the same value, maintained incrementally by
+/// an [`AggregateStorage`].
+///
+/// `()` can be used when only the number of entities with each value is needed.
+///
+/// Since inputs must be removable, folds such as a minimum or maximum can't be kept as a single
+/// number. Instead, use an output that remembers every input, such as a
+/// [`BTreeMap`][std::collections::BTreeMap] from each input to the number of entities with it, and
+/// read the first or last key.
+pub trait Aggregate<C>: Send + Sync + 'static {
+    /// The part of a component that is folded, e.g. a field to be summed.
+    type Input: Send + Sync + 'static;
+    /// The result of the fold for each value.
+    type Output: Default + Send + Sync + 'static;
+
+    /// Extract the input from a component.
+    fn input(component: &C) -> Self::Input;
+
+    /// Update the output when an entity gains the value.
+    fn add(output: &mut Self::Output, input: &Self::Input);
+
+    /// Update the output when an entity loses the value. `input` is the same input that was
+    /// previously passed to [`add`][Aggregate::add].
+    fn remove(output: &mut Self::Output, input: &Self::Input);
+}
+
+impl<C> Aggregate<C> for () {
+    type Input = ();
+    type Output = ();
+
+    fn input(_: &C) {}
+
+    fn add(_: &mut (), _: &()) {}
+
+    fn remove(_: &mut (), _: &()) {}
+}
+
+/// Storages that maintain an [`Aggregate`] for each value, such as [`AggregateStorage`] and
+/// [`ViewStorage`][super::ViewStorage].
+///
+/// Like other storage methods, these never refresh the storage. See
+/// [reading storages directly][crate::storage#reading-storages-directly].
+pub trait AggregatingStorage<I: IndexInfo>: IndexStorage<I> {
+    /// The aggregate maintained for each value.
+    type Aggregate: Aggregate<I::Component>;
+
+    /// Get the number of entities with the given value.
+    fn count(&self, val: &I::Value) -> usize;
+
+    /// Get the aggregate of the components of all entities with the given value, or `None` if no
+    /// entities have the value.
+    fn aggregate(
+        &self,
+        val: &I::Value,
+    ) -> Option<&<Self::Aggregate as Aggregate<I::Component>>::Output>;
+}
+
+/// The output of the [`Aggregate`] maintained by an index's [`AggregatingStorage`].
+pub type AggregateOutput<I> =
+    <<<I as IndexInfo>::Storage as AggregatingStorage<I>>::Aggregate as Aggregate<
+        <I as IndexInfo>::Component,
+    >>::Output;
+
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+struct AggregateGroup<T> {
+    count: usize,
+    // Only kept when the storage tracks the entities with each value.
+    entities: Option<HashSet<Entity>>,
+    output: T,
+}
+
+/// [`IndexStorage`] implementation that only keeps track of how many entities have each value,
+/// along with an optional [`Aggregate`] of their components.
+///
+/// Each entity's value and [`Aggregate::Input`] are still stored, since they are needed to update
+/// the counts and aggregates when the entity is removed, but the entities with each value are not.
+/// This means [`Index::lookup`] has to check every entity in the index, so this storage should
+/// only be used for indexes where [`Index::count`] and [`Index::aggregate`] are all that is needed.
+/// Use [`ViewStorage`][super::ViewStorage] to look up entities too. Requires the index's values to
+/// implement [`Hash`] and [`Eq`].
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct AggregateStorage<I: IndexInfo, A: Aggregate<I::Component> = ()> {
+    groups: HashMap<I::Value, AggregateGroup<A::Output>>,
+    entities: HashMap<Entity, (I::Value, A::Input)>,
+    track_entities: bool,
+    cache: CacheState,
+    #[cfg_attr(feature = "reflect", reflect(ignore))]
+    phantom: PhantomData<fn() -> A>,
+}
+
+impl<I: IndexInfo, A: Aggregate<I::Component>> Default for AggregateStorage<I, A> {
+    fn default() -> Self {
+        Self {
+            groups: Default::default(),
+            entities: Default::default(),
+            track_entities: false,
+            cache: Default::default(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo, A: Aggregate<I::Component>> CachedStorage<I> for AggregateStorage<I, A>
+where
+    I::Value: Hash + Eq,
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, component: &I::Component) {
+        CachedStorage::<I>::remove(self, &entity);
+        let input = A::input(component);
+        let track_entities = self.track_entities;
+        let group = self
+            .groups
+            .entry(value.clone())
+            .or_insert_with(|| AggregateGroup {
+                count: 0,
+                entities: track_entities.then(HashSet::default),
+                output: Default::default(),
+            });
+        group.count += 1;
+        if let Some(entities) = &mut group.entities {
+            entities.insert(entity);
+        }
+        A::add(&mut group.output, &input);
+        self.entities.insert(entity, (value.clone(), input));
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        let Some((value, input)) = self.entities.remove(entity) else {
+            return;
+        };
+        let group = self
+            .groups
+            .get_mut(&value)
+            .expect("Storage is in an invalid state: entity is missing from its value.");
+        group.count -= 1;
+        if group.count == 0 {
+            self.groups.remove(&value);
+        } else {
+            if let Some(entities) = &mut group.entities {
+                entities.remove(entity);
+            }
+            A::remove(&mut group.output, &input);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.groups.clear();
+        self.entities.clear();
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        let matches: Vec<Entity> = if self.track_entities {
+            self.groups
+                .get(val)
+                .and_then(|group| group.entities.as_ref())
+                .into_iter()
+                .flatten()
+                .copied()
+                .collect()
+        } else {
+            self.entities
+                .iter()
+                .filter(|(_, (v, _))| v == val)
+                .map(|(e, _)| *e)
+                .collect()
+        };
+        matches.into_iter()
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        Some(
+            self.entities
+                .iter()
+                .map(|(entity, (val, _))| (*entity, val.clone())),
+        )
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.entities.len())
+    }
+
+    fn bucket_count(&self) -> Option<usize> {
+        Some(self.groups.len())
+    }
+}
+
+impl_cached_index_storage!(
+    AggregateStorage<I, A> where I::Value: Hash + Eq, A: Aggregate<I::Component>
+);
+
+impl<I: IndexInfo, A: Aggregate<I::Component>> AggregatingStorage<I> for AggregateStorage<I, A>
+where
+    I::Value: Hash + Eq,
+{
+    type Aggregate = A;
+
+    fn count(&self, val: &I::Value) -> usize {
+        self.groups.get(val).map_or(0, |group| group.count)
+    }
+
+    fn aggregate(&self, val: &I::Value) -> Option<&A::Output> {
+        self.groups.get(val).map(|group| &group.output)
+    }
+}
+
+impl<I: IndexInfo, A: Aggregate<I::Component>> AggregateStorage<I, A>
+where
+    I::Value: Hash + Eq,
+{
+    /// Create a storage that also keeps the entities with each value, for
+    /// [`ViewStorage`][super::ViewStorage].
+    pub(crate) fn tracking_entities() -> Self {
+        Self {
+            track_entities: true,
+            ..Default::default()
+        }
+    }
+
+    /// Iterate over every value in the index along with its count and aggregate.
+    pub fn iter(&self) -> impl Iterator<Item = (&I::Value, usize, &A::Output)> {
+        self.groups
+            .iter()
+            .map(|(val, group)| (val, group.count, &group.output))
+    }
+
+    /// Iterate over every value in the index along with its entities and aggregate. The entities
+    /// are only known if the storage was created with [`tracking_entities`][Self::tracking_entities].
+    pub(crate) fn iter_entities(
+        &self,
+    ) -> impl Iterator<Item = (&I::Value, impl Iterator<Item = Entity> + '_, &A::Output)> {
+        self.groups
+            .iter()
+            .map(|(val, group)| (val, group.entities.iter().flatten().copied(), &group.output))
+    }
+
+    /// Get the value that an entity is currently indexed under, if any.
+    pub(crate) fn value_of(&self, entity: &Entity) -> Option<&I::Value> {
+        self.entities.get(entity).map(|(val, _)| val)
+    }
+}
+
+impl<'w, 's, I: IndexInfo> Index<'w, 's, I>
+where
+    I::Storage: AggregatingStorage<I>,
+{
+    /// Get the number of entities with the given value.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn count(&mut self, val: &I::Value) -> usize {
+        self.prepare_storage();
+        self.storage.count(val)
+    }
+
+    /// Get the aggregate of the components of all entities with the given value, or `None` if no
+    /// entities have the value.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn aggregate(&mut self, val: &I::Value) -> Option<&AggregateOutput<I>> {
+        self.prepare_storage();
+        self.storage.aggregate(val)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use bevy::ecs::system::RunSystemOnce;
+    use std::collections::BTreeMap;
+
+    #[derive(Component)]
+    struct Member {
+        team: u32,
+        gold: u32,
+    }
+
+    struct TotalGold;
+    impl Aggregate<Member> for TotalGold {
+        type Input = u32;
+        type Output = u32;
+
+        fn input(component: &Member) -> u32 {
+            component.gold
+        }
+
+        fn add(output: &mut u32, input: &u32) {
+            *output += input;
+        }
+
+        fn remove(output: &mut u32, input: &u32) {
+            *output -= input;
+        }
+    }
+
+    // Counts of each amount of gold, so that the richest member is known even after they leave.
+    struct RichestMember;
+    impl Aggregate<Member> for RichestMember {
+        type Input = u32;
+        type Output = BTreeMap<u32, usize>;
+
+        fn input(component: &Member) -> u32 {
+            component.gold
+        }
+
+        fn add(output: &mut BTreeMap<u32, usize>, input: &u32) {
+            *output.entry(*input).or_default() += 1;
+        }
+
+        fn remove(output: &mut BTreeMap<u32, usize>, input: &u32) {
+            let count = output.get_mut(input).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                output.remove(input);
+            }
+        }
+    }
+
+    struct TeamRichest;
+    impl IndexInfo for TeamRichest {
+        type Component = Member;
+        type Value = u32;
+        type Storage = AggregateStorage<Self, RichestMember>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Member, _: &()) -> u32 {
+            c.team
+        }
+    }
+
+    struct TeamGold;
+    impl IndexInfo for TeamGold {
+        type Component = Member;
+        type Value = u32;
+        type Storage = AggregateStorage<Self, TotalGold>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Member, _: &()) -> u32 {
+            c.team
+        }
+    }
+
+    struct TeamSize;
+    impl IndexInfo for TeamSize {
+        type Component = Member;
+        type Value = u32;
+        type Storage = AggregateStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenInserted;
+
+        fn value(c: &Member, _: &()) -> u32 {
+            c.team
+        }
+    }
+
+    fn team(world: &mut World, team: u32) -> (usize, Option<u32>, usize) {
+        world
+            .run_system_once(
+                move |mut gold: Index<TeamGold>, mut size: Index<TeamSize>| {
+                    (
+                        gold.count(&team),
+                        gold.aggregate(&team).copied(),
+                        size.count(&team),
+                    )
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_aggregate_storage() {
+        let mut world = World::new();
+        let a = world.spawn(Member { team: 1, gold: 10 }).id();
+        world.spawn(Member { team: 1, gold: 5 });
+        let c = world.spawn(Member { team: 2, gold: 7 }).id();
+
+        assert_eq!(team(&mut world, 1), (2, Some(15), 2));
+        assert_eq!(team(&mut world, 2), (1, Some(7), 1));
+        assert_eq!(team(&mut world, 3), (0, None, 0));
+
+        world.get_mut::<Member>(a).unwrap().gold = 20;
+        assert_eq!(team(&mut world, 1), (2, Some(25), 2));
+
+        world.entity_mut(a).insert(Member { team: 2, gold: 1 });
+        world.despawn(c);
+        assert_eq!(team(&mut world, 1), (1, Some(5), 1));
+        assert_eq!(team(&mut world, 2), (1, Some(1), 1));
+
+        let members = world
+            .run_system_once(|mut idx: Index<TeamGold>| idx.lookup(&2).collect::<Vec<_>>())
+            .unwrap();
+        assert_eq!(members, vec![a]);
+    }
+
+    #[test]
+    fn test_aggregate_storage_min_max() {
+        let mut world = World::new();
+        let a = world.spawn(Member { team: 1, gold: 10 }).id();
+        world.spawn(Member { team: 1, gold: 5 });
+        world.spawn(Member { team: 1, gold: 10 });
+        let d = world.spawn(Member { team: 1, gold: 20 }).id();
+
+        let min_max = |world: &mut World| {
+            world
+                .run_system_once(|mut idx: Index<TeamRichest>| {
+                    idx.aggregate(&1).map(|gold| {
+                        (
+                            *gold.first_key_value().unwrap().0,
+                            *gold.last_key_value().unwrap().0,
+                        )
+                    })
+                })
+                .unwrap()
+        };
+        assert_eq!(min_max(&mut world), Some((5, 20)));
+
+        world.despawn(d);
+        assert_eq!(min_max(&mut world), Some((5, 10)));
+        world.despawn(a);
+        assert_eq!(min_max(&mut world), Some((5, 10)));
+    }
+}
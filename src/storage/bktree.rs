@@ -0,0 +1,368 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use std::marker::PhantomData;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// A distance function between values, used to organize a [`BkTreeStorage`].
+///
+/// The distance must be a metric for lookups to be correct: it must be `0` exactly when the
+/// values are equal, be the same in both directions, and satisfy the triangle inequality.
+pub trait Metric<V>: Send + Sync + 'static {
+    /// Get the distance between two values.
+    fn distance(a: &V, b: &V) -> usize;
+}
+
+/// [`Metric`] that measures the number of single-character insertions, deletions, and
+/// substitutions needed to turn one string into another.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct Levenshtein;
+
+impl<V: AsRef<str>> Metric<V> for Levenshtein {
+    fn distance(a: &V, b: &V) -> usize {
+        let b: Vec<char> = b.as_ref().chars().collect();
+        // Distances from a prefix of `a` to every prefix of `b`, one row at a time.
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, a_char) in a.as_ref().chars().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for (j, b_char) in b.iter().enumerate() {
+                let substitution = diagonal + usize::from(a_char != *b_char);
+                diagonal = row[j + 1];
+                row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+            }
+        }
+        row[b.len()]
+    }
+}
+
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+struct BkNode<V> {
+    value: V,
+    entities: HashSet<Entity>,
+    // Child node indices, keyed by their distance from this node.
+    children: HashMap<usize, usize>,
+}
+
+/// [`IndexStorage`] implementation that organizes values in a BK-tree, allowing entities to be
+/// looked up by values that are close to, but not exactly equal to, a given value.
+///
+/// Closeness is measured with a [`Metric`], which defaults to [`Levenshtein`] distance for
+/// string values.
+///
+/// Values that no entities are indexed under anymore are kept in the tree until they make up
+/// most of it, at which point the tree is rebuilt.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct BkTreeStorage<I: IndexInfo, M = Levenshtein> {
+    nodes: Vec<BkNode<I::Value>>,
+    rev_map: HashMap<Entity, usize>,
+    empty_nodes: usize,
+    cache: CacheState,
+    #[cfg_attr(feature = "reflect", reflect(ignore))]
+    phantom: PhantomData<fn() -> M>,
+}
+
+impl<I: IndexInfo, M> Default for BkTreeStorage<I, M> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            rev_map: Default::default(),
+            empty_nodes: 0,
+            cache: Default::default(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo, M: Metric<I::Value>> CachedStorage<I> for BkTreeStorage<I, M> {
+    fn insert(&mut self, entity: Entity, value: &I::Value, _component: &I::Component) {
+        if let Some(&old) = self.rev_map.get(&entity) {
+            if self.nodes[old].value == *value {
+                return;
+            }
+            self.remove_from_node(old, &entity);
+        }
+        let len = self.nodes.len();
+        let idx = self.find_or_insert(value);
+        let node = &mut self.nodes[idx];
+        if idx < len && node.entities.is_empty() {
+            // Reusing a node that was left in the tree.
+            self.empty_nodes -= 1;
+        }
+        node.entities.insert(entity);
+        self.rev_map.insert(entity, idx);
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        if let Some(idx) = self.rev_map.remove(entity) {
+            self.remove_from_node(idx, entity);
+            if self.empty_nodes > 16 && self.empty_nodes * 2 > self.nodes.len() {
+                self.compact();
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.rev_map.clear();
+        self.empty_nodes = 0;
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        self.find(val)
+            .into_iter()
+            .flat_map(|idx| self.nodes[idx].entities.iter().copied())
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        Some(self.nodes.iter().flat_map(|node| {
+            node.entities
+                .iter()
+                .map(move |entity| (*entity, node.value.clone()))
+        }))
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.rev_map.len())
+    }
+
+    fn bucket_count(&self) -> Option<usize> {
+        Some(self.nodes.len() - self.empty_nodes)
+    }
+}
+
+impl_cached_index_storage!(BkTreeStorage<I, M> where M: Metric<I::Value>);
+
+impl<I: IndexInfo, M: Metric<I::Value>> BkTreeStorage<I, M> {
+    /// Get the value that an entity is currently indexed under, if any.
+    pub fn value_of(&self, entity: &Entity) -> Option<&I::Value> {
+        self.rev_map.get(entity).map(|&idx| &self.nodes[idx].value)
+    }
+
+    /// Get all of the entities whose values are at most `max_dist` away from the given value,
+    /// along with their distance from it.
+    ///
+    /// Results are not returned in any particular order.
+    pub fn lookup_within_distance(
+        &self,
+        val: &I::Value,
+        max_dist: usize,
+    ) -> impl Iterator<Item = (Entity, usize)> + '_ {
+        let mut matches = Vec::new();
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            let dist = M::distance(val, &node.value);
+            if dist <= max_dist && !node.entities.is_empty() {
+                matches.push((idx, dist));
+            }
+            // By the triangle inequality, only children whose distance from this node is within
+            // `max_dist` of `dist` can contain matches.
+            for (&child_dist, &child) in node.children.iter() {
+                if child_dist.abs_diff(dist) <= max_dist {
+                    stack.push(child);
+                }
+            }
+        }
+        matches.into_iter().flat_map(move |(idx, dist)| {
+            self.nodes[idx]
+                .entities
+                .iter()
+                .map(move |&entity| (entity, dist))
+        })
+    }
+
+    fn find(&self, val: &I::Value) -> Option<usize> {
+        let mut idx = 0;
+        loop {
+            let node = self.nodes.get(idx)?;
+            match M::distance(val, &node.value) {
+                0 => return Some(idx),
+                dist => idx = *node.children.get(&dist)?,
+            }
+        }
+    }
+
+    fn find_or_insert(&mut self, val: &I::Value) -> usize {
+        let new_idx = self.nodes.len();
+        let new_node = || BkNode {
+            value: val.clone(),
+            entities: HashSet::default(),
+            children: HashMap::default(),
+        };
+        if self.nodes.is_empty() {
+            self.nodes.push(new_node());
+            return new_idx;
+        }
+
+        let mut idx = 0;
+        loop {
+            let node = &mut self.nodes[idx];
+            match M::distance(val, &node.value) {
+                0 => return idx,
+                dist => match node.children.get(&dist) {
+                    Some(&child) => idx = child,
+                    None => {
+                        node.children.insert(dist, new_idx);
+                        self.nodes.push(new_node());
+                        return new_idx;
+                    }
+                },
+            }
+        }
+    }
+
+    fn remove_from_node(&mut self, idx: usize, entity: &Entity) {
+        let entities = &mut self.nodes[idx].entities;
+        entities.remove(entity);
+        if entities.is_empty() {
+            self.empty_nodes += 1;
+        }
+    }
+
+    // Rebuilds the tree without the values that no longer have any entities.
+    fn compact(&mut self) {
+        let nodes = std::mem::take(&mut self.nodes);
+        self.empty_nodes = 0;
+        for node in nodes {
+            if node.entities.is_empty() {
+                continue;
+            }
+            let idx = self.find_or_insert(&node.value);
+            for entity in node.entities.iter() {
+                self.rev_map.insert(*entity, idx);
+            }
+            self.nodes[idx].entities = node.entities;
+        }
+    }
+}
+
+impl<'w, 's, I, M> Index<'w, 's, I>
+where
+    I: IndexInfo<Storage = BkTreeStorage<I, M>>,
+    M: Metric<I::Value>,
+{
+    /// Get all of the entities whose values are at most `max_dist` away from the given value,
+    /// along with their distance from it.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn lookup_within_distance(
+        &mut self,
+        val: &I::Value,
+        max_dist: usize,
+    ) -> impl Iterator<Item = (Entity, usize)> + '_ {
+        self.prepare_storage();
+        self.storage.lookup_within_distance(val, max_dist)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    struct Word(String);
+
+    impl Word {
+        fn new(word: &str) -> Self {
+            Self(word.to_string())
+        }
+    }
+
+    struct WordIndex;
+    impl IndexInfo for WordIndex {
+        type Component = Word;
+        type Value = String;
+        type Storage = BkTreeStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Word, _: &()) -> String {
+            c.0.clone()
+        }
+    }
+
+    fn fuzzy(world: &mut World, word: &'static str, max_dist: usize) -> Vec<(Entity, usize)> {
+        world
+            .run_system_once(move |mut idx: Index<WordIndex>| {
+                let mut matches: Vec<_> = idx
+                    .lookup_within_distance(&word.to_string(), max_dist)
+                    .collect();
+                matches.sort();
+                matches
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        let dist = <Levenshtein as Metric<&str>>::distance;
+        assert_eq!(dist(&"kitten", &"sitting"), 3);
+        assert_eq!(dist(&"gobln", &"goblin"), 1);
+        assert_eq!(dist(&"", &"orc"), 3);
+        assert_eq!(dist(&"orc", &"orc"), 0);
+    }
+
+    #[test]
+    fn test_bktree_storage() {
+        let mut world = World::new();
+        let goblin = world.spawn(Word::new("goblin")).id();
+        let gobbo = world.spawn(Word::new("gobbo")).id();
+        world.spawn(Word::new("orc"));
+        let goblin2 = world.spawn(Word::new("goblin")).id();
+
+        assert_eq!(fuzzy(&mut world, "gobln", 0), vec![]);
+        assert_eq!(
+            fuzzy(&mut world, "gobln", 1),
+            vec![(goblin, 1), (goblin2, 1)]
+        );
+        assert_eq!(
+            fuzzy(&mut world, "gobln", 2),
+            vec![(goblin, 1), (gobbo, 2), (goblin2, 1)]
+        );
+        let exact = world
+            .run_system_once(|mut idx: Index<WordIndex>| idx.lookup(&"gobbo".into()).count())
+            .unwrap();
+        assert_eq!(exact, 1);
+
+        world.despawn(goblin2);
+        world.entity_mut(goblin).insert(Word::new("ogre"));
+        assert_eq!(fuzzy(&mut world, "gobln", 2), vec![(gobbo, 2)]);
+        assert_eq!(fuzzy(&mut world, "ogres", 1), vec![(goblin, 1)]);
+    }
+
+    #[test]
+    fn test_bktree_compaction() {
+        let mut world = World::new();
+        let words: Vec<Entity> = (0..64)
+            .map(|i| world.spawn(Word(format!("word{i}"))).id())
+            .collect();
+        let kept = world.spawn(Word::new("zzz")).id();
+        fuzzy(&mut world, "", 0);
+        assert_eq!(world.resource::<BkTreeStorage<WordIndex>>().nodes.len(), 65);
+
+        for entity in words {
+            world.despawn(entity);
+        }
+        assert_eq!(fuzzy(&mut world, "zz", 1), vec![(kept, 1)]);
+        assert_eq!(fuzzy(&mut world, "word1", 1), vec![]);
+        assert!(world.resource::<BkTreeStorage<WordIndex>>().nodes.len() < 33);
+    }
+}
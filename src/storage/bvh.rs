@@ -0,0 +1,548 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::math::bounding::{
+    Aabb2d,
+    Aabb3d,
+    BoundingVolume,
+    IntersectsVolume,
+    RayCast2d,
+    RayCast3d,
+};
+use bevy::math::Vec3A;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// Bounding boxes that can be stored in a [`BvhStorage`].
+///
+/// This is implemented for [`Aabb2d`] and [`Aabb3d`].
+pub trait BvhBounds: BoundingVolume + IntersectsVolume<Self> + Copy {
+    /// The type of points that boxes can contain.
+    type Point: Copy;
+    /// The type of rays that can be cast against boxes.
+    type RayCast;
+
+    /// Returns `true` if the point is inside or on the edge of the box.
+    fn contains_point(&self, point: Self::Point) -> bool;
+
+    /// Returns the distance along the ray at which it hits the box, if it does.
+    fn ray_hit(&self, ray: &Self::RayCast) -> Option<f32>;
+}
+
+impl BvhBounds for Aabb2d {
+    type Point = Vec2;
+    type RayCast = RayCast2d;
+
+    fn contains_point(&self, point: Vec2) -> bool {
+        self.min.cmple(point).all() && point.cmple(self.max).all()
+    }
+
+    fn ray_hit(&self, ray: &RayCast2d) -> Option<f32> {
+        ray.aabb_intersection_at(self)
+    }
+}
+
+impl BvhBounds for Aabb3d {
+    type Point = Vec3;
+    type RayCast = RayCast3d;
+
+    fn contains_point(&self, point: Vec3) -> bool {
+        let point = Vec3A::from(point);
+        self.min.cmple(point).all() && point.cmple(self.max).all()
+    }
+
+    fn ray_hit(&self, ray: &RayCast3d) -> Option<f32> {
+        ray.aabb_intersection_at(self)
+    }
+}
+
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+enum BvhNodeKind {
+    Leaf(Entity),
+    Branch(usize, usize),
+    Free,
+}
+
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+struct BvhNode<B> {
+    // For branches, the smallest box containing both children.
+    bounds: B,
+    parent: Option<usize>,
+    kind: BvhNodeKind,
+}
+
+/// [`IndexStorage`] implementation for values that are bounding boxes, such as [`Aabb2d`] or
+/// [`Aabb3d`], that allows looking up the entities whose boxes intersect another box, contain a
+/// point, or are hit by a ray.
+///
+/// Boxes are kept in a bounding volume hierarchy, which is updated incrementally as entities'
+/// values change.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct BvhStorage<I: IndexInfo> {
+    nodes: Vec<BvhNode<I::Value>>,
+    free_nodes: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<Entity, usize>,
+    cache: CacheState,
+}
+
+impl<I: IndexInfo> Default for BvhStorage<I> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            root: None,
+            leaves: Default::default(),
+            cache: Default::default(),
+        }
+    }
+}
+
+impl<I: IndexInfo> CachedStorage<I> for BvhStorage<I>
+where
+    I::Value: BvhBounds,
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, _component: &I::Component) {
+        if let Some(&leaf) = self.leaves.get(&entity) {
+            if self.nodes[leaf].bounds == *value {
+                return;
+            }
+            self.remove_leaf(leaf);
+        }
+        let leaf = self.insert_leaf(entity, *value);
+        self.leaves.insert(entity, leaf);
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        if let Some(leaf) = self.leaves.remove(entity) {
+            self.remove_leaf(leaf);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.free_nodes.clear();
+        self.root = None;
+        self.leaves.clear();
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        let matches: Vec<Entity> = self
+            .search(|bounds| bounds.contains(val))
+            .filter(|(bounds, _)| *bounds == val)
+            .map(|(_, entity)| entity)
+            .collect();
+        matches.into_iter()
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        Some(
+            self.leaves
+                .iter()
+                .map(|(entity, &leaf)| (*entity, self.nodes[leaf].bounds)),
+        )
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.leaves.len())
+    }
+}
+
+impl_cached_index_storage!(BvhStorage<I> where I::Value: BvhBounds);
+
+impl<I: IndexInfo> BvhStorage<I>
+where
+    I::Value: BvhBounds,
+{
+    /// Get the value that an entity is currently indexed under, if any.
+    pub fn value_of(&self, entity: &Entity) -> Option<&I::Value> {
+        self.leaves
+            .get(entity)
+            .map(|&leaf| &self.nodes[leaf].bounds)
+    }
+
+    /// Get all of the entities whose boxes intersect the given box.
+    pub fn lookup_intersecting<'a>(
+        &'a self,
+        bounds: &'a I::Value,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        self.search(|node| node.intersects(bounds))
+            .map(|(_, entity)| entity)
+    }
+
+    /// Get all of the entities whose boxes contain the given point.
+    pub fn lookup_containing_point(
+        &self,
+        point: <I::Value as BvhBounds>::Point,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.search(move |node| node.contains_point(point))
+            .map(|(_, entity)| entity)
+    }
+
+    /// Get all of the entities whose boxes are hit by the given ray, along with the distance
+    /// along the ray at which they are hit, ordered from nearest to farthest.
+    pub fn lookup_ray(&self, ray: &<I::Value as BvhBounds>::RayCast) -> Vec<(Entity, f32)> {
+        let mut hits: Vec<(Entity, f32)> = self
+            .search(|node| node.ray_hit(ray).is_some())
+            .filter_map(|(bounds, entity)| Some((entity, bounds.ray_hit(ray)?)))
+            .collect();
+        hits.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        hits
+    }
+
+    // Visits the leaves whose boxes, and whose ancestors' boxes, satisfy `filter`.
+    fn search<'a>(
+        &'a self,
+        filter: impl Fn(&I::Value) -> bool + 'a,
+    ) -> impl Iterator<Item = (&'a I::Value, Entity)> + 'a {
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        std::iter::from_fn(move || {
+            while let Some(idx) = stack.pop() {
+                let node = &self.nodes[idx];
+                if !filter(&node.bounds) {
+                    continue;
+                }
+                match node.kind {
+                    BvhNodeKind::Leaf(entity) => return Some((&node.bounds, entity)),
+                    BvhNodeKind::Branch(a, b) => stack.extend([a, b]),
+                    BvhNodeKind::Free => unreachable!("Free nodes are not part of the tree."),
+                }
+            }
+            None
+        })
+    }
+
+    fn alloc(&mut self, node: BvhNode<I::Value>) -> usize {
+        match self.free_nodes.pop() {
+            Some(idx) => {
+                self.nodes[idx] = node;
+                idx
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn free(&mut self, idx: usize) {
+        self.nodes[idx].kind = BvhNodeKind::Free;
+        self.free_nodes.push(idx);
+    }
+
+    fn insert_leaf(&mut self, entity: Entity, bounds: I::Value) -> usize {
+        let leaf = self.alloc(BvhNode {
+            bounds,
+            parent: None,
+            kind: BvhNodeKind::Leaf(entity),
+        });
+        let Some(root) = self.root else {
+            self.root = Some(leaf);
+            return leaf;
+        };
+
+        // Find the node that the new leaf will become a sibling of, trying to keep the total
+        // area of the branches that contain it small.
+        let mut sibling = root;
+        while let BvhNodeKind::Branch(a, b) = self.nodes[sibling].kind {
+            let area = self.nodes[sibling].bounds.visible_area();
+            let combined_area = self.nodes[sibling].bounds.merge(&bounds).visible_area();
+            // Cost of making the new leaf a sibling of this node.
+            let cost = 2. * combined_area;
+            // Cost of growing this node to contain the new leaf, which must be paid to descend.
+            let inherited_cost = 2. * (combined_area - area);
+            let child_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let combined_area = child.bounds.merge(&bounds).visible_area();
+                let growth = match child.kind {
+                    BvhNodeKind::Leaf(_) => combined_area,
+                    _ => combined_area - child.bounds.visible_area(),
+                };
+                growth + inherited_cost
+            };
+            let (cost_a, cost_b) = (child_cost(a), child_cost(b));
+            if cost < cost_a && cost < cost_b {
+                break;
+            }
+            sibling = if cost_a <= cost_b { a } else { b };
+        }
+
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.alloc(BvhNode {
+            bounds: self.nodes[sibling].bounds.merge(&bounds),
+            parent: old_parent,
+            kind: BvhNodeKind::Branch(sibling, leaf),
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+        match old_parent {
+            Some(old_parent) => self.replace_child(old_parent, sibling, parent),
+            None => self.root = Some(parent),
+        }
+        self.refit(old_parent);
+        leaf
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        let parent = self.nodes[leaf].parent;
+        self.free(leaf);
+        let Some(parent) = parent else {
+            self.root = None;
+            return;
+        };
+
+        let sibling = match self.nodes[parent].kind {
+            BvhNodeKind::Branch(a, b) if a == leaf => b,
+            BvhNodeKind::Branch(a, _) => a,
+            _ => unreachable!("Parents are always branches."),
+        };
+        let grandparent = self.nodes[parent].parent;
+        self.free(parent);
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => {
+                self.replace_child(grandparent, parent, sibling);
+                self.refit(Some(grandparent));
+            }
+            None => self.root = Some(sibling),
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let BvhNodeKind::Branch(a, b) = &mut self.nodes[parent].kind {
+            if *a == old {
+                *a = new;
+            } else {
+                *b = new;
+            }
+        }
+    }
+
+    // Recomputes the boxes of a branch and all of its ancestors.
+    fn refit(&mut self, mut branch: Option<usize>) {
+        while let Some(idx) = branch {
+            if let BvhNodeKind::Branch(a, b) = self.nodes[idx].kind {
+                self.nodes[idx].bounds = self.nodes[a].bounds.merge(&self.nodes[b].bounds);
+            }
+            branch = self.nodes[idx].parent;
+        }
+    }
+}
+
+impl<'w, 's, I: IndexInfo<Storage = BvhStorage<I>>> Index<'w, 's, I>
+where
+    I::Value: BvhBounds,
+{
+    /// Get all of the entities whose boxes intersect the given box.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn lookup_intersecting<'a>(
+        &'a mut self,
+        bounds: &'a I::Value,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        self.prepare_storage();
+        self.storage.lookup_intersecting(bounds)
+    }
+
+    /// Get all of the entities whose boxes contain the given point.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn lookup_containing_point(
+        &mut self,
+        point: <I::Value as BvhBounds>::Point,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        self.prepare_storage();
+        self.storage.lookup_containing_point(point)
+    }
+
+    /// Get all of the entities whose boxes are hit by the given ray, along with the distance
+    /// along the ray at which they are hit, ordered from nearest to farthest.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn lookup_ray(&mut self, ray: &<I::Value as BvhBounds>::RayCast) -> Vec<(Entity, f32)> {
+        self.prepare_storage();
+        self.storage.lookup_ray(ray)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::test_util::{lookup_sorted, sorted};
+    use bevy::ecs::system::RunSystemOnce;
+
+    // Boxes are centered on the translation and sized by the scale.
+    struct BoxIndex;
+    impl IndexInfo for BoxIndex {
+        type Component = Transform;
+        type Value = Aabb2d;
+        type Storage = BvhStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(t: &Transform, _: &()) -> Aabb2d {
+            Aabb2d::new(t.translation.truncate(), t.scale.truncate() / 2.)
+        }
+    }
+
+    // The same boxes in 3D.
+    struct VolumeIndex;
+    impl IndexInfo for VolumeIndex {
+        type Component = Transform;
+        type Value = Aabb3d;
+        type Storage = BvhStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(t: &Transform, _: &()) -> Aabb3d {
+            Aabb3d::new(t.translation, t.scale / 2.)
+        }
+    }
+
+    fn spawn_box(world: &mut World, x: f32, y: f32, size: f32) -> Entity {
+        world
+            .spawn(Transform::from_xyz(x, y, 0.).with_scale(Vec3::splat(size)))
+            .id()
+    }
+
+    fn intersecting(world: &mut World, min: Vec2, max: Vec2) -> Vec<Entity> {
+        lookup_sorted::<BoxIndex>(world, move |idx| {
+            idx.lookup_intersecting(&Aabb2d { min, max }).collect()
+        })
+    }
+
+    fn containing(world: &mut World, point: Vec2) -> Vec<Entity> {
+        lookup_sorted::<BoxIndex>(world, move |idx| {
+            idx.lookup_containing_point(point).collect()
+        })
+    }
+
+    #[test]
+    fn test_bvh_storage() {
+        let mut world = World::new();
+        let a = spawn_box(&mut world, 0., 0., 2.);
+        let b = spawn_box(&mut world, 3., 0., 2.);
+        let c = spawn_box(&mut world, 10., 10., 4.);
+
+        assert_eq!(containing(&mut world, Vec2::ZERO), vec![a]);
+        assert_eq!(containing(&mut world, Vec2::new(2.5, 0.5)), vec![b]);
+        assert_eq!(containing(&mut world, Vec2::new(5., 5.)), vec![]);
+        assert_eq!(
+            intersecting(&mut world, Vec2::new(0.5, -1.), Vec2::new(2.5, 1.)),
+            vec![a, b]
+        );
+        assert_eq!(
+            intersecting(&mut world, Vec2::splat(-20.), Vec2::splat(20.)),
+            vec![a, b, c]
+        );
+
+        let hits = world
+            .run_system_once(|mut idx: Index<BoxIndex>| {
+                idx.lookup_ray(&RayCast2d::new(Vec2::new(-5., 0.), Dir2::X, 100.))
+            })
+            .unwrap();
+        assert_eq!(hits, vec![(a, 4.), (b, 7.)]);
+
+        world.get_mut::<Transform>(c).unwrap().translation = Vec3::new(5., 5., 0.);
+        world.despawn(a);
+        assert_eq!(containing(&mut world, Vec2::new(5., 5.)), vec![c]);
+        assert_eq!(containing(&mut world, Vec2::ZERO), vec![]);
+        let exact = lookup_sorted::<BoxIndex>(&mut world, |idx| {
+            idx.lookup(&Aabb2d::new(Vec2::new(3., 0.), Vec2::ONE))
+                .collect()
+        });
+        assert_eq!(exact, vec![b]);
+    }
+
+    #[test]
+    fn test_bvh_storage_many() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..200)
+            .map(|i| spawn_box(&mut world, (i % 20) as f32 * 3., (i / 20) as f32 * 3., 2.))
+            .collect();
+        for &entity in entities.iter().step_by(3) {
+            world.despawn(entity);
+        }
+
+        let expected: Vec<Entity> = sorted(
+            entities
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| i % 3 != 0 && (i % 20) < 4 && (i / 20) < 4)
+                .map(|(_, e)| *e),
+        );
+        assert_eq!(
+            intersecting(&mut world, Vec2::splat(-1.), Vec2::splat(10.)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_bvh_storage_3d() {
+        let mut world = World::new();
+        let spawn_cube = |world: &mut World, translation: Vec3| {
+            world
+                .spawn(Transform::from_translation(translation).with_scale(Vec3::splat(2.)))
+                .id()
+        };
+        let a = spawn_cube(&mut world, Vec3::ZERO);
+        let b = spawn_cube(&mut world, Vec3::new(0., 0., 5.));
+        let c = spawn_cube(&mut world, Vec3::new(0., 5., 5.));
+
+        let intersecting = |world: &mut World, center: Vec3, half_size: f32| {
+            lookup_sorted::<VolumeIndex>(world, move |idx| {
+                idx.lookup_intersecting(&Aabb3d::new(center, Vec3::splat(half_size)))
+                    .collect()
+            })
+        };
+        assert_eq!(
+            intersecting(&mut world, Vec3::new(0., 0., 2.5), 2.),
+            vec![a, b]
+        );
+        assert_eq!(intersecting(&mut world, Vec3::new(0., 5., 0.), 1.), vec![]);
+        assert_eq!(
+            lookup_sorted::<VolumeIndex>(&mut world, |idx| {
+                idx.lookup_containing_point(Vec3::new(0.5, 5.5, 4.5))
+                    .collect()
+            }),
+            vec![c]
+        );
+
+        let ray = |world: &mut World, origin: Vec3, direction: Dir3| {
+            world
+                .run_system_once(move |mut idx: Index<VolumeIndex>| {
+                    idx.lookup_ray(&RayCast3d::new(origin, direction, 100.))
+                })
+                .unwrap()
+        };
+        assert_eq!(
+            ray(&mut world, Vec3::new(0., 0., -10.), Dir3::Z),
+            vec![(a, 9.), (b, 14.)]
+        );
+        assert_eq!(
+            ray(&mut world, Vec3::new(0., 10., 5.), Dir3::NEG_Y),
+            vec![(c, 4.), (b, 9.)]
+        );
+
+        // Boxes that move out of the ray's path are no longer hit.
+        world.get_mut::<Transform>(a).unwrap().translation = Vec3::new(5., 0., 0.);
+        assert_eq!(
+            ray(&mut world, Vec3::new(0., 0., -10.), Dir3::Z),
+            vec![(b, 14.)]
+        );
+    }
+}
@@ -0,0 +1,378 @@
+use crate::index::{IndexData, IndexInfo};
+use crate::storage::StorageStats;
+use bevy::ecs::component::Tick;
+use bevy::ecs::entity::{EntityMapper, MapEntities};
+use bevy::ecs::entity_disabling::Disabled;
+use bevy::ecs::system::{RunSystemOnce, StaticSystemParam, SystemChangeTick, SystemParam};
+use bevy::platform::time::Instant;
+use bevy::prelude::*;
+use std::time::Duration;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// Storages that cache the value computed for each entity, and keep it up to date using
+/// change detection and observers according to the index's
+/// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`].
+///
+/// All of the caching storages provided by this crate, such as [`HashmapStorage`][super::HashmapStorage],
+/// implement this trait and share the same refresh behavior.
+pub trait CachedStorage<I: IndexInfo>: Resource + Default {
+    /// Store the value computed for an entity, replacing its previous value if it had one.
+    fn insert(&mut self, entity: Entity, value: &I::Value, component: &I::Component);
+
+    /// Remove an entity from the storage if it is present.
+    fn remove(&mut self, entity: &Entity);
+
+    /// Remove all entities from the storage.
+    fn clear(&mut self);
+
+    /// Get all of the entities cached under the given value.
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity>;
+
+    /// Get every cached entity along with its cached value, used for [`IndexStorage::entries`][crate::storage::IndexStorage::entries].
+    ///
+    /// Returns `None` by default, for storages that can't recover the values they were given.
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        None::<std::iter::Empty<_>>
+    }
+
+    /// Bookkeeping used to refresh the storage.
+    fn cache_state(&mut self) -> &mut CacheState;
+
+    /// Get the number of entities in the storage, if it is cheap to compute.
+    fn entity_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// Get the number of distinct values in the storage, if it is cheap to compute.
+    fn bucket_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// Bookkeeping data used to refresh a [`CachedStorage`].
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct CacheState {
+    last_refresh_tick: Tick,
+    removed_entities: Vec<Entity>,
+    // Set when the storage's contents didn't come from refreshing it, e.g. a loaded snapshot.
+    needs_rebuild: bool,
+    // Totals since the storage's stats were last taken.
+    refresh_time: Duration,
+    refreshed_entities: usize,
+}
+
+impl Default for CacheState {
+    fn default() -> Self {
+        Self {
+            last_refresh_tick: Tick::new(0),
+            removed_entities: Vec::with_capacity(16),
+            needs_rebuild: false,
+            refresh_time: Duration::ZERO,
+            refreshed_entities: 0,
+        }
+    }
+}
+
+impl CacheState {
+    // Makes the next refresh rebuild the storage from scratch instead of applying changes.
+    #[cfg(feature = "serde")]
+    pub(crate) fn invalidate(&mut self) {
+        self.needs_rebuild = true;
+    }
+}
+
+impl MapEntities for CacheState {
+    fn map_entities<E: EntityMapper>(&mut self, entity_mapper: &mut E) {
+        for entity in &mut self.removed_entities {
+            *entity = entity_mapper.get_mapped(*entity);
+        }
+    }
+}
+
+// Includes disabled entities so that they can be indexed if `INCLUDE_DISABLED` is set.
+type ComponentsQuery<'w, 's, T> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        Ref<'static, <T as IndexInfo>::Component>,
+        Has<Disabled>,
+    ),
+>;
+type DataParam<'w, 's, T> = StaticSystemParam<'w, 's, <T as IndexInfo>::Data>;
+
+#[doc(hidden)]
+#[derive(SystemParam)]
+pub struct CachedStorageRefreshData<'w, 's, I: IndexInfo> {
+    components: ComponentsQuery<'w, 's, I>,
+    data: DataParam<'w, 's, I>,
+    ticks: SystemChangeTick,
+}
+
+// Indexes refreshed by observers are never refreshed automatically, so this should be called
+// before lookups to check if their values need to be recomputed.
+pub(crate) fn prepare_lookup<I: IndexInfo, S: CachedStorage<I>>(
+    storage: &mut S,
+    data: &mut StaticSystemParam<CachedStorageRefreshData<I>>,
+) {
+    if I::REFRESH_POLICY.is_when_inserted()
+        && (storage.cache_state().needs_rebuild || data_changed(storage, data))
+    {
+        rebuild(storage, data);
+    }
+}
+
+pub(crate) fn refresh<I: IndexInfo, S: CachedStorage<I>>(
+    storage: &mut S,
+    data: &mut StaticSystemParam<CachedStorageRefreshData<I>>,
+) {
+    if storage.cache_state().last_refresh_tick != data.ticks.this_run() {
+        force_refresh(storage, data);
+    }
+}
+
+pub(crate) fn force_refresh<I: IndexInfo, S: CachedStorage<I>>(
+    storage: &mut S,
+    data: &mut StaticSystemParam<CachedStorageRefreshData<I>>,
+) {
+    index_span!("index_refresh", I);
+    let start = Instant::now();
+    if storage.cache_state().needs_rebuild || data_changed(storage, data) {
+        rebuild(storage, data);
+    } else {
+        update(storage, data);
+    }
+    storage.cache_state().refresh_time += start.elapsed();
+}
+
+// Applies the changes made since the last refresh.
+fn update<I: IndexInfo, S: CachedStorage<I>>(
+    storage: &mut S,
+    data: &mut StaticSystemParam<CachedStorageRefreshData<I>>,
+) {
+    let removed = std::mem::take(&mut storage.cache_state().removed_entities);
+    for entity in removed.iter() {
+        storage.remove(entity);
+    }
+    // Keep the allocation around for next time.
+    storage.cache_state().removed_entities = removed;
+    storage.cache_state().removed_entities.clear();
+
+    let last_refresh_tick = storage.cache_state().last_refresh_tick;
+    let mut refreshed = 0;
+    for (entity, component, disabled) in &data.components {
+        if disabled && !I::INCLUDE_DISABLED {
+            continue;
+        }
+        if component.last_changed().is_newer_than(
+            // Subtract 1 so that changes from the system where the index was updated are seen.
+            // The `is_newer_than` implementation assumes we don't care about those changes since
+            // "this" system is the one that made the change, but for indexing, we do care.
+            Tick::new(last_refresh_tick.get().wrapping_sub(1)),
+            data.ticks.this_run(),
+        ) {
+            storage.insert(entity, &I::value(&component, &data.data), &component);
+            refreshed += 1;
+        }
+    }
+    let state = storage.cache_state();
+    state.refreshed_entities += refreshed;
+    state.last_refresh_tick = data.ticks.this_run();
+}
+
+// Checks if the index's data has changed since the last time the storage was refreshed.
+fn data_changed<I: IndexInfo, S: CachedStorage<I>>(
+    storage: &mut S,
+    data: &mut StaticSystemParam<CachedStorageRefreshData<I>>,
+) -> bool {
+    I::Data::is_changed(
+        &data.data,
+        Tick::new(
+            storage
+                .cache_state()
+                .last_refresh_tick
+                .get()
+                .wrapping_sub(1),
+        ),
+        data.ticks.this_run(),
+    )
+}
+
+// Throws away all cached values and recomputes them from scratch.
+fn rebuild<I: IndexInfo, S: CachedStorage<I>>(
+    storage: &mut S,
+    data: &mut StaticSystemParam<CachedStorageRefreshData<I>>,
+) {
+    storage.clear();
+    storage.cache_state().removed_entities.clear();
+    storage.cache_state().needs_rebuild = false;
+    let mut refreshed = 0;
+    for (entity, component, disabled) in &data.components {
+        if disabled && !I::INCLUDE_DISABLED {
+            continue;
+        }
+        storage.insert(entity, &I::value(&component, &data.data), &component);
+        refreshed += 1;
+    }
+    let state = storage.cache_state();
+    state.refreshed_entities += refreshed;
+    state.last_refresh_tick = data.ticks.this_run();
+}
+
+pub(crate) fn take_stats<I: IndexInfo, S: CachedStorage<I>>(storage: &mut S) -> StorageStats {
+    let entities = storage.entity_count();
+    let buckets = storage.bucket_count();
+    let state = storage.cache_state();
+    StorageStats {
+        refresh_time: Some(std::mem::take(&mut state.refresh_time)),
+        refreshed_entities: Some(std::mem::take(&mut state.refreshed_entities)),
+        entities,
+        buckets,
+        pending_removals: Some(state.removed_entities.len()),
+    }
+}
+
+pub(crate) fn insertion_observer<I: IndexInfo, S: CachedStorage<I>>() -> Option<Observer> {
+    if I::REFRESH_POLICY.is_when_inserted() {
+        Some(Observer::new(
+            |trigger: Trigger<OnInsert, I::Component>,
+             mut storage: ResMut<S>,
+             components: Query<(&I::Component, Has<Disabled>)>,
+             data: StaticSystemParam<I::Data>| {
+                index_span!("index_insertion_observer", I);
+                let target = trigger.target();
+                let (component, disabled) = components
+                    .get(target)
+                    .expect("Component that was just inserted is missing!");
+
+                if !disabled || I::INCLUDE_DISABLED {
+                    storage.insert(target, &I::value(component, &data), component);
+                }
+            },
+        ))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn removal_observer<I: IndexInfo, S: CachedStorage<I>>() -> Option<Observer> {
+    Some(Observer::new(
+        |trigger: Trigger<OnRemove, I::Component>, mut storage: ResMut<S>| {
+            index_span!("index_removal_observer", I);
+            if I::REFRESH_POLICY.is_when_inserted() {
+                storage.remove(&trigger.target());
+            } else {
+                storage
+                    .cache_state()
+                    .removed_entities
+                    .push(trigger.target());
+            }
+        },
+    ))
+}
+
+pub(crate) fn disabling_observers<I: IndexInfo, S: CachedStorage<I>>() -> Vec<Observer> {
+    if I::INCLUDE_DISABLED {
+        return Vec::new();
+    }
+    vec![
+        Observer::new(
+            |trigger: Trigger<OnInsert, Disabled>,
+             mut storage: ResMut<S>,
+             components: Query<(), (With<I::Component>, With<Disabled>)>| {
+                if components.contains(trigger.target()) {
+                    storage.remove(&trigger.target());
+                }
+            },
+        ),
+        Observer::new(
+            |trigger: Trigger<OnRemove, Disabled>,
+             components: Query<(), (With<I::Component>, With<Disabled>)>,
+             mut commands: Commands| {
+                let target = trigger.target();
+                if components.contains(target) {
+                    // `Disabled` is also removed when the entity is despawned, so wait until it is
+                    // actually gone to see whether the entity should be added back.
+                    commands.queue(move |world: &mut World| {
+                        world
+                            .run_system_once_with(reenable::<I, S>, target)
+                            .expect("Failed to re-enable entity in index.");
+                    });
+                }
+            },
+        ),
+    ]
+}
+
+// Adds an entity back to the storage after it has been re-enabled.
+fn reenable<I: IndexInfo, S: CachedStorage<I>>(
+    In(entity): In<Entity>,
+    mut storage: ResMut<S>,
+    components: Query<&I::Component>,
+    data: StaticSystemParam<I::Data>,
+) {
+    if let Ok(component) = components.get(entity) {
+        storage.insert(entity, &I::value(component, &data), component);
+    }
+}
+
+// Implements `IndexStorage` for a `CachedStorage` by delegating to the shared refresh logic.
+macro_rules! impl_cached_index_storage {
+    ($storage:ident<I $(, $param:ident)*> $(where $($bound:tt)+)?) => {
+        impl<I: IndexInfo $(, $param)*> IndexStorage<I> for $storage<I $(, $param)*>
+        $(where $($bound)+)?
+        {
+            type RefreshData<'w, 's> = $crate::storage::CachedStorageRefreshData<'w, 's, I>;
+
+            fn lookup<'w, 's>(
+                &mut self,
+                val: &I::Value,
+                data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>,
+            ) -> impl Iterator<Item = Entity> {
+                $crate::storage::cached::prepare_lookup(self, data);
+                $crate::storage::CachedStorage::cached_lookup(self, val)
+            }
+
+            fn refresh<'w, 's>(&mut self, data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>) {
+                $crate::storage::cached::refresh(self, data)
+            }
+
+            fn entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+                $crate::storage::CachedStorage::cached_entries(self)
+            }
+
+            fn prepare_lookup<'w, 's>(
+                &mut self,
+                data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>,
+            ) {
+                $crate::storage::cached::prepare_lookup(self, data)
+            }
+
+            fn force_refresh<'w, 's>(
+                &mut self,
+                data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>,
+            ) {
+                $crate::storage::cached::force_refresh(self, data)
+            }
+
+            fn insertion_observer() -> Option<Observer> {
+                $crate::storage::cached::insertion_observer::<I, Self>()
+            }
+
+            fn removal_observer() -> Option<Observer> {
+                $crate::storage::cached::removal_observer::<I, Self>()
+            }
+
+            fn disabling_observers() -> Vec<Observer> {
+                $crate::storage::cached::disabling_observers::<I, Self>()
+            }
+
+            fn take_stats(&mut self) -> $crate::storage::StorageStats {
+                $crate::storage::cached::take_stats(self)
+            }
+        }
+    };
+}
+pub(crate) use impl_cached_index_storage;
@@ -0,0 +1,291 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use crate::unique_multimap::UniqueMultiMap;
+use bevy::ecs::system::StaticSystemParam;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use std::hash::Hash;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// Tuple values that can be looked up by any of their leading elements in a [`CompositeStorage`].
+///
+/// Implemented for tuples of 2 to 4 elements that each implement [`Hash`], [`Eq`], and [`Clone`].
+pub trait CompositeKey: Hash + Eq + Clone {
+    /// Maps from each proper prefix of the key to the entities with that prefix.
+    #[doc(hidden)]
+    type PrefixMaps: Default + Send + Sync + 'static;
+
+    #[doc(hidden)]
+    fn add_prefixes(&self, maps: &mut Self::PrefixMaps, entity: Entity);
+
+    #[doc(hidden)]
+    fn remove_prefixes(&self, maps: &mut Self::PrefixMaps, entity: &Entity);
+}
+
+/// A leading subset of a [`CompositeKey`], such as `(zone,)` or `(zone, faction)` for a
+/// `(zone, faction, rank)` key.
+pub trait KeyPrefix<K: CompositeKey> {
+    #[doc(hidden)]
+    fn entities<'a>(&self, maps: &'a K::PrefixMaps) -> Option<&'a HashSet<Entity>>;
+}
+
+#[doc(hidden)]
+pub struct PrefixMap<P>(HashMap<P, HashSet<Entity>>);
+
+impl<P> Default for PrefixMap<P> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<P: Hash + Eq> PrefixMap<P> {
+    fn add(&mut self, prefix: P, entity: Entity) {
+        self.0.entry(prefix).or_default().insert(entity);
+    }
+
+    fn remove(&mut self, prefix: &P, entity: &Entity) {
+        let entities = self
+            .0
+            .get_mut(prefix)
+            .expect("Storage is in an invalid state: entity is missing from its prefix.");
+        entities.remove(entity);
+        if entities.is_empty() {
+            self.0.remove(prefix);
+        }
+    }
+}
+
+macro_rules! impl_composite_key {
+    (($($ty:ident),+) { $($map:tt: ($($pty:ident),+) [$($idx:tt),+]),+ $(,)? }) => {
+        impl<$($ty),+> CompositeKey for ($($ty,)+)
+        where
+            $($ty: Hash + Eq + Clone + Send + Sync + 'static),+
+        {
+            type PrefixMaps = ($(PrefixMap<($($pty,)+)>,)+);
+
+            fn add_prefixes(&self, maps: &mut Self::PrefixMaps, entity: Entity) {
+                $(maps.$map.add(($(self.$idx.clone(),)+), entity);)+
+            }
+
+            fn remove_prefixes(&self, maps: &mut Self::PrefixMaps, entity: &Entity) {
+                $(maps.$map.remove(&($(self.$idx.clone(),)+), entity);)+
+            }
+        }
+
+        impl_composite_key!(@prefixes ($($ty),+) $($map ($($pty),+)),+);
+    };
+    (@prefixes $key:tt $($map:tt $prefix:tt),+) => {
+        $(impl_composite_key!(@prefix $key $map $prefix);)+
+    };
+    (@prefix ($($ty:ident),+) $map:tt ($($pty:ident),+)) => {
+        impl<$($ty),+> KeyPrefix<($($ty,)+)> for ($($pty,)+)
+        where
+            $($ty: Hash + Eq + Clone + Send + Sync + 'static),+
+        {
+            fn entities<'a>(
+                &self,
+                maps: &'a <($($ty,)+) as CompositeKey>::PrefixMaps,
+            ) -> Option<&'a HashSet<Entity>> {
+                maps.$map.0.get(self)
+            }
+        }
+    };
+}
+
+impl_composite_key!((A, B) { 0: (A) [0] });
+impl_composite_key!((A, B, C) { 0: (A) [0], 1: (A, B) [0, 1] });
+impl_composite_key!((A, B, C, D) { 0: (A) [0], 1: (A, B) [0, 1], 2: (A, B, C) [0, 1, 2] });
+
+/// [`IndexStorage`] implementation for tuple values that supports looking up entities by their full
+/// value or by any leading subset of it with [`Index::lookup_key_prefix`].
+///
+/// Each prefix is stored in its own map, so prefix lookups are as fast as full lookups, at the
+/// cost of updating one map per element of the key whenever a value changes. Requires the index's
+/// values to implement [`CompositeKey`].
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct CompositeStorage<I: IndexInfo>
+where
+    I::Value: CompositeKey,
+{
+    map: UniqueMultiMap<I::Value, Entity>,
+    #[cfg_attr(feature = "reflect", reflect(ignore))]
+    prefixes: <I::Value as CompositeKey>::PrefixMaps,
+    cache: CacheState,
+}
+
+impl<I: IndexInfo> Default for CompositeStorage<I>
+where
+    I::Value: CompositeKey,
+{
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+            prefixes: Default::default(),
+            cache: Default::default(),
+        }
+    }
+}
+
+impl<I: IndexInfo> CachedStorage<I> for CompositeStorage<I>
+where
+    I::Value: CompositeKey,
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, _component: &I::Component) {
+        if self.map.get_key(&entity) == Some(value) {
+            return;
+        }
+        if let Some(old_value) = self.map.insert(value, entity) {
+            old_value.remove_prefixes(&mut self.prefixes, &entity);
+        }
+        value.add_prefixes(&mut self.prefixes, entity);
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        if let Some(old_value) = self.map.remove(entity) {
+            old_value.remove_prefixes(&mut self.prefixes, entity);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.map = Default::default();
+        self.prefixes = Default::default();
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        self.map.get(val).copied()
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        Some(
+            self.map
+                .iter()
+                .flat_map(|(val, entities)| entities.map(move |entity| (*entity, val.clone()))),
+        )
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.map.len())
+    }
+
+    fn bucket_count(&self) -> Option<usize> {
+        Some(self.map.key_count())
+    }
+}
+
+impl_cached_index_storage!(CompositeStorage<I> where I::Value: CompositeKey);
+
+impl<I: IndexInfo> CompositeStorage<I>
+where
+    I::Value: CompositeKey,
+{
+    /// Get the value that an entity is currently indexed under, if any.
+    pub fn value_of(&self, entity: &Entity) -> Option<&I::Value> {
+        self.map.get_key(entity)
+    }
+
+    /// Get all of the entities whose values start with the given prefix.
+    pub fn lookup_key_prefix<'a, P: KeyPrefix<I::Value>>(
+        &'a self,
+        prefix: &P,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        prefix
+            .entities(&self.prefixes)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+}
+
+impl<'w, 's, I: IndexInfo<Storage = CompositeStorage<I>>> Index<'w, 's, I>
+where
+    I::Value: CompositeKey,
+{
+    /// Get all of the entities whose values start with the given prefix, e.g. `(zone,)` or
+    /// `(zone, faction)` for an index of `(zone, faction, rank)` values.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn lookup_key_prefix<'a, P: KeyPrefix<I::Value>>(
+        &'a mut self,
+        prefix: &P,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        self.prepare_storage();
+        self.storage.lookup_key_prefix(prefix)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::test_util::sorted;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    struct Soldier {
+        zone: u32,
+        faction: u32,
+        rank: u32,
+    }
+
+    struct SoldierIndex;
+    impl IndexInfo for SoldierIndex {
+        type Component = Soldier;
+        type Value = (u32, u32, u32);
+        type Storage = CompositeStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Soldier, _: &()) -> (u32, u32, u32) {
+            (c.zone, c.faction, c.rank)
+        }
+    }
+
+    #[test]
+    fn test_composite_storage() {
+        let mut world = World::new();
+        let soldier = |zone, faction, rank| Soldier {
+            zone,
+            faction,
+            rank,
+        };
+        let a = world.spawn(soldier(1, 1, 1)).id();
+        let b = world.spawn(soldier(1, 1, 2)).id();
+        let c = world.spawn(soldier(1, 2, 1)).id();
+        let d = world.spawn(soldier(2, 1, 1)).id();
+
+        let lookups = |world: &mut World| {
+            world
+                .run_system_once(|mut idx: Index<SoldierIndex>| {
+                    (
+                        sorted(idx.lookup(&(1, 1, 1))),
+                        sorted(idx.lookup_key_prefix(&(1, 1))),
+                        sorted(idx.lookup_key_prefix(&(1,))),
+                        sorted(idx.lookup_key_prefix(&(2,))),
+                    )
+                })
+                .unwrap()
+        };
+
+        assert_eq!(
+            lookups(&mut world),
+            (vec![a], vec![a, b], vec![a, b, c], vec![d])
+        );
+
+        world.get_mut::<Soldier>(b).unwrap().zone = 2;
+        world.despawn(c);
+        assert_eq!(lookups(&mut world), (vec![a], vec![a], vec![a], vec![b, d]));
+
+        world.get_mut::<Soldier>(a).unwrap().rank = 3;
+        assert_eq!(lookups(&mut world), (vec![], vec![a], vec![a], vec![b, d]));
+    }
+}
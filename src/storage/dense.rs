@@ -0,0 +1,193 @@
+use crate::index::IndexInfo;
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::prelude::*;
+use std::marker::PhantomData;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// Values that map to a small range of `usize`s, for use with [`DenseStorage`].
+///
+/// Implement this for enums or bounded integers whose values are all close to zero. Equal values
+/// must map to the same index, and different values must map to different indexes.
+pub trait DenseKey {
+    /// Get the index of this value's bucket.
+    fn to_index(&self) -> usize;
+}
+
+macro_rules! impl_dense_key {
+    ($($ty:ty),*) => {
+        $(
+            impl DenseKey for $ty {
+                fn to_index(&self) -> usize {
+                    *self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_dense_key!(bool, u8, u16, u32, usize);
+
+/// [`IndexStorage`] implementation for values that implement [`DenseKey`], such as small enums.
+///
+/// Entities are stored in a bucket for each value, and their positions are stored by their
+/// [`Entity::index`], so lookups and updates never need to hash anything. Memory use grows with
+/// the largest [`DenseKey::to_index`] and the largest entity index in the index.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct DenseStorage<I: IndexInfo> {
+    buckets: Vec<Vec<Entity>>,
+    // The bucket and position in that bucket of each entity, by entity index.
+    locations: Vec<Option<(u32, u32)>>,
+    cache: CacheState,
+    #[cfg_attr(feature = "reflect", reflect(ignore))]
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: IndexInfo> Default for DenseStorage<I> {
+    fn default() -> Self {
+        Self {
+            buckets: Vec::new(),
+            locations: Vec::new(),
+            cache: Default::default(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo> CachedStorage<I> for DenseStorage<I>
+where
+    I::Value: DenseKey,
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, _component: &I::Component) {
+        let bucket = value.to_index();
+        if let Some((old_bucket, _)) = self.location(&entity) {
+            if old_bucket as usize == bucket {
+                return;
+            }
+            CachedStorage::<I>::remove(self, &entity);
+        }
+
+        if self.buckets.len() <= bucket {
+            self.buckets.resize_with(bucket + 1, Vec::new);
+        }
+        let entity_index = entity.index() as usize;
+        if self.locations.len() <= entity_index {
+            self.locations.resize(entity_index + 1, None);
+        }
+        let entities = &mut self.buckets[bucket];
+        self.locations[entity_index] = Some((bucket as u32, entities.len() as u32));
+        entities.push(entity);
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        let Some((bucket, pos)) = self.location(entity) else {
+            return;
+        };
+        self.locations[entity.index() as usize] = None;
+        let entities = &mut self.buckets[bucket as usize];
+        entities.swap_remove(pos as usize);
+        if let Some(moved) = entities.get(pos as usize) {
+            self.locations[moved.index() as usize] = Some((bucket, pos));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.buckets.clear();
+        self.locations.clear();
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        self.buckets
+            .get(val.to_index())
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.buckets.iter().map(Vec::len).sum())
+    }
+
+    fn bucket_count(&self) -> Option<usize> {
+        Some(self.buckets.iter().filter(|b| !b.is_empty()).count())
+    }
+}
+
+impl_cached_index_storage!(DenseStorage<I> where I::Value: DenseKey);
+
+impl<I: IndexInfo> DenseStorage<I> {
+    // Finds where an entity is stored, ignoring other entities that share its index.
+    fn location(&self, entity: &Entity) -> Option<(u32, u32)> {
+        let (bucket, pos) = (*self.locations.get(entity.index() as usize)?)?;
+        (self.buckets[bucket as usize][pos as usize] == *entity).then_some((bucket, pos))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::test_util::lookup_sorted;
+
+    #[derive(Component, Clone, Copy, PartialEq, Debug)]
+    enum Team {
+        Red,
+        Blue,
+        Green,
+    }
+
+    impl DenseKey for Team {
+        fn to_index(&self) -> usize {
+            *self as usize
+        }
+    }
+
+    impl IndexInfo for Team {
+        type Component = Team;
+        type Value = Team;
+        type Storage = DenseStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Team, _: &()) -> Team {
+            *c
+        }
+    }
+
+    fn members(world: &mut World, team: Team) -> Vec<Entity> {
+        lookup_sorted::<Team>(world, move |idx| idx.lookup(&team).collect())
+    }
+
+    #[test]
+    fn test_dense_storage() {
+        let mut world = World::new();
+        let red1 = world.spawn(Team::Red).id();
+        let red2 = world.spawn(Team::Red).id();
+        let blue = world.spawn(Team::Blue).id();
+        let red3 = world.spawn(Team::Red).id();
+
+        assert_eq!(members(&mut world, Team::Red), vec![red1, red2, red3]);
+        assert_eq!(members(&mut world, Team::Blue), vec![blue]);
+        assert_eq!(members(&mut world, Team::Green), vec![]);
+
+        *world.get_mut::<Team>(red1).unwrap() = Team::Green;
+        world.despawn(red2);
+        assert_eq!(members(&mut world, Team::Red), vec![red3]);
+        assert_eq!(members(&mut world, Team::Green), vec![red1]);
+
+        // The despawned entity's index is reused.
+        let green = world.spawn(Team::Green).id();
+        assert_eq!(green.index(), red2.index());
+        assert_eq!(members(&mut world, Team::Green), vec![red1, green]);
+        assert_eq!(members(&mut world, Team::Red), vec![red3]);
+    }
+}
@@ -0,0 +1,403 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::prelude::*;
+use std::marker::PhantomData;
+use std::ops::{BitAnd, BitOr, Sub};
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// A set of entities stored as a bitset of their [`Entity::index`]es.
+///
+/// Sets can be combined with `&` (intersection), `|` (union), and `-` (difference), e.g. to find
+/// the entities that are flagged by several [`FlagStorage`] indexes. A set can only hold one
+/// entity with each index, so if both sets hold different entities with the same index, the
+/// result keeps the one from the left-hand set.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[derive(Clone, Default, Debug)]
+pub struct EntityBitSet {
+    bits: Vec<u64>,
+    // The entity at each set bit's index. Other slots hold stale or placeholder entities.
+    entities: Vec<Entity>,
+    len: usize,
+}
+
+impl EntityBitSet {
+    /// Returns `true` if the entity is in the set.
+    pub fn contains(&self, entity: Entity) -> bool {
+        let index = entity.index() as usize;
+        self.has_bit(index) && self.entities[index] == entity
+    }
+
+    /// Get the number of entities in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no entities in the set.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the entities in the set, in order of their indexes.
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.bits
+            .iter()
+            .enumerate()
+            .flat_map(move |(word_idx, &word)| {
+                BitIter(word).map(move |bit| self.entities[word_idx * 64 + bit])
+            })
+    }
+
+    /// Add an entity to the set, returning `true` if it was not already present.
+    ///
+    /// Replaces any other entity with the same index.
+    pub fn insert(&mut self, entity: Entity) -> bool {
+        let index = entity.index() as usize;
+        if self.has_bit(index) {
+            let replaced = self.entities[index] != entity;
+            self.entities[index] = entity;
+            return replaced;
+        }
+        let word = index / 64;
+        if self.bits.len() <= word {
+            self.bits.resize(word + 1, 0);
+        }
+        if self.entities.len() <= index {
+            self.entities.resize(index + 1, Entity::PLACEHOLDER);
+        }
+        self.bits[word] |= 1 << (index % 64);
+        self.entities[index] = entity;
+        self.len += 1;
+        true
+    }
+
+    /// Remove an entity from the set, returning `true` if it was present.
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        if !self.contains(entity) {
+            return false;
+        }
+        let index = entity.index() as usize;
+        self.bits[index / 64] &= !(1 << (index % 64));
+        self.len -= 1;
+        true
+    }
+
+    /// Remove all entities from the set.
+    pub fn clear(&mut self) {
+        self.bits.clear();
+        self.entities.clear();
+        self.len = 0;
+    }
+
+    fn has_bit(&self, index: usize) -> bool {
+        self.bits
+            .get(index / 64)
+            .is_some_and(|word| word & (1 << (index % 64)) != 0)
+    }
+
+    // Builds a set from the words produced by `op`, keeping the entities from `self` or `other`.
+    fn combine(&self, other: &Self, op: impl Fn(u64, u64) -> u64) -> Self {
+        let words = self.bits.len().max(other.bits.len());
+        let mut result = Self {
+            bits: Vec::with_capacity(words),
+            entities: vec![Entity::PLACEHOLDER; words * 64],
+            len: 0,
+        };
+        for word_idx in 0..words {
+            let (a, b) = (
+                self.bits.get(word_idx).copied().unwrap_or(0),
+                other.bits.get(word_idx).copied().unwrap_or(0),
+            );
+            // Bits set in both sets only refer to the same entity if their generations match.
+            let mut conflicts = 0;
+            for bit in BitIter(a & b) {
+                let index = word_idx * 64 + bit;
+                if self.entities[index] != other.entities[index] {
+                    conflicts |= 1 << bit;
+                }
+            }
+            let word = op(a, b & !conflicts) | (op(a, 0) & conflicts);
+            for bit in BitIter(word) {
+                let index = word_idx * 64 + bit;
+                result.entities[index] = if a & (1 << bit) != 0 {
+                    self.entities[index]
+                } else {
+                    other.entities[index]
+                };
+            }
+            result.len += word.count_ones() as usize;
+            result.bits.push(word);
+        }
+        result
+    }
+}
+
+impl BitAnd for &EntityBitSet {
+    type Output = EntityBitSet;
+
+    fn bitand(self, rhs: Self) -> EntityBitSet {
+        self.combine(rhs, |a, b| a & b)
+    }
+}
+
+impl BitOr for &EntityBitSet {
+    type Output = EntityBitSet;
+
+    fn bitor(self, rhs: Self) -> EntityBitSet {
+        self.combine(rhs, |a, b| a | b)
+    }
+}
+
+impl Sub for &EntityBitSet {
+    type Output = EntityBitSet;
+
+    fn sub(self, rhs: Self) -> EntityBitSet {
+        self.combine(rhs, |a, b| a & !b)
+    }
+}
+
+// Iterates over the positions of the set bits in a word.
+struct BitIter(u64);
+
+impl Iterator for BitIter {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let bit = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(bit)
+    }
+}
+
+/// [`IndexStorage`] implementation for indexes with `bool` values, which stores the entities for
+/// each value in an [`EntityBitSet`].
+///
+/// In addition to normal lookups, the set of flagged entities (those whose value is `true`) can be
+/// accessed directly to check membership, count them, or combine them with other flag indexes.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct FlagStorage<I: IndexInfo> {
+    flagged: EntityBitSet,
+    unflagged: EntityBitSet,
+    cache: CacheState,
+    #[cfg_attr(feature = "reflect", reflect(ignore))]
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: IndexInfo> Default for FlagStorage<I> {
+    fn default() -> Self {
+        Self {
+            flagged: Default::default(),
+            unflagged: Default::default(),
+            cache: Default::default(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo<Value = bool>> CachedStorage<I> for FlagStorage<I> {
+    fn insert(&mut self, entity: Entity, value: &bool, _component: &I::Component) {
+        if *value {
+            self.unflagged.remove(entity);
+            self.flagged.insert(entity);
+        } else {
+            self.flagged.remove(entity);
+            self.unflagged.insert(entity);
+        }
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        self.flagged.remove(*entity);
+        self.unflagged.remove(*entity);
+    }
+
+    fn clear(&mut self) {
+        self.flagged.clear();
+        self.unflagged.clear();
+    }
+
+    fn cached_lookup(&self, val: &bool) -> impl Iterator<Item = Entity> {
+        if *val { &self.flagged } else { &self.unflagged }.iter()
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, bool)>> {
+        Some(
+            self.flagged
+                .iter()
+                .map(|entity| (entity, true))
+                .chain(self.unflagged.iter().map(|entity| (entity, false))),
+        )
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.flagged.len() + self.unflagged.len())
+    }
+}
+
+impl_cached_index_storage!(FlagStorage<I> where I: IndexInfo<Value = bool>);
+
+impl<I: IndexInfo<Value = bool>> FlagStorage<I> {
+    /// Get the set of entities whose value is `true`.
+    pub fn flagged(&self) -> &EntityBitSet {
+        &self.flagged
+    }
+
+    /// Get the set of entities whose value is `false`.
+    pub fn unflagged(&self) -> &EntityBitSet {
+        &self.unflagged
+    }
+}
+
+impl<'w, 's, I: IndexInfo<Value = bool, Storage = FlagStorage<I>>> Index<'w, 's, I> {
+    /// Get the set of entities whose value is `true`.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn flagged(&mut self) -> &EntityBitSet {
+        self.prepare_storage();
+        self.storage.flagged()
+    }
+
+    /// Get the set of entities whose value is `false`.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn unflagged(&mut self) -> &EntityBitSet {
+        self.prepare_storage();
+        self.storage.unflagged()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    struct Stats {
+        health: u32,
+        stealthy: bool,
+    }
+
+    struct Alive;
+    impl IndexInfo for Alive {
+        type Component = Stats;
+        type Value = bool;
+        type Storage = FlagStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Stats, _: &()) -> bool {
+            c.health > 0
+        }
+    }
+
+    struct Stealthy;
+    impl IndexInfo for Stealthy {
+        type Component = Stats;
+        type Value = bool;
+        type Storage = FlagStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Stats, _: &()) -> bool {
+            c.stealthy
+        }
+    }
+
+    #[test]
+    fn test_entity_bitset() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..100).map(|_| world.spawn_empty().id()).collect();
+        let mut evens = EntityBitSet::default();
+        let mut threes = EntityBitSet::default();
+        for (i, &entity) in entities.iter().enumerate() {
+            if i % 2 == 0 {
+                evens.insert(entity);
+            }
+            if i % 3 == 0 {
+                threes.insert(entity);
+            }
+        }
+        assert_eq!(evens.len(), 50);
+        assert!(evens.contains(entities[98]));
+        assert!(!evens.contains(entities[99]));
+
+        let sixes = &evens & &threes;
+        assert_eq!(sixes.len(), 17);
+        assert!(sixes.iter().eq(entities.iter().copied().step_by(6)));
+        assert_eq!((&evens | &threes).len(), 67);
+        assert_eq!((&evens - &threes).len(), 33);
+
+        // A different entity with the same index is not the same member.
+        world.despawn(entities[0]);
+        let reused = world.spawn_empty().id();
+        let mut other = EntityBitSet::default();
+        other.insert(reused);
+        assert!(!other.contains(entities[0]));
+        assert!((&evens & &other).is_empty());
+        assert_eq!((&evens | &other).len(), 50);
+        assert_eq!((&evens - &other).len(), 50);
+    }
+
+    #[test]
+    fn test_flag_storage() {
+        let mut world = World::new();
+        let ninja = world
+            .spawn(Stats {
+                health: 10,
+                stealthy: true,
+            })
+            .id();
+        let knight = world
+            .spawn(Stats {
+                health: 20,
+                stealthy: false,
+            })
+            .id();
+        let ghost = world
+            .spawn(Stats {
+                health: 0,
+                stealthy: true,
+            })
+            .id();
+
+        let living_stealthy = |world: &mut World| {
+            world
+                .run_system_once(|mut alive: Index<Alive>, mut stealthy: Index<Stealthy>| {
+                    (alive.flagged() & stealthy.flagged())
+                        .iter()
+                        .collect::<Vec<_>>()
+                })
+                .unwrap()
+        };
+        assert_eq!(living_stealthy(&mut world), vec![ninja]);
+
+        let (alive, dead) = world
+            .run_system_once(|mut alive: Index<Alive>| {
+                (
+                    alive.flagged().len(),
+                    alive.lookup(&false).collect::<Vec<_>>(),
+                )
+            })
+            .unwrap();
+        assert_eq!(alive, 2);
+        assert_eq!(dead, vec![ghost]);
+
+        world.get_mut::<Stats>(knight).unwrap().stealthy = true;
+        world.get_mut::<Stats>(ninja).unwrap().health = 0;
+        assert_eq!(living_stealthy(&mut world), vec![knight]);
+    }
+}
@@ -0,0 +1,346 @@
+use crate::index::IndexInfo;
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use crate::unique_multimap::UniqueMultiMap;
+use bevy::ecs::entity::{EntityMapper, MapEntities};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::prelude::*;
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// [`IndexStorage`] implementation that maintains a HashMap from values to [`Entity`]s whose
+/// components have that value.
+///
+/// Requires the index's values to implement [`Hash`] and [`Eq`].
+///
+/// With the `serde` feature, the storage's contents can be serialized as a list of values, each with
+/// the entities indexed under it. Use [`sorted_snapshot`][HashmapStorage::sorted_snapshot] to
+/// serialize the values in sorted order. A deserialized storage is used as is until its next
+/// refresh, which rebuilds it from the world. Since entities are usually remapped when a save is loaded,
+/// use [`MapEntities`] to update a deserialized storage with the new entities.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct HashmapStorage<I: IndexInfo> {
+    map: UniqueMultiMap<I::Value, Entity>,
+    cache: CacheState,
+}
+
+impl<I: IndexInfo> Default for HashmapStorage<I> {
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+            cache: Default::default(),
+        }
+    }
+}
+
+impl<I: IndexInfo> CachedStorage<I> for HashmapStorage<I>
+where
+    I::Value: Hash + Eq,
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, _component: &I::Component) {
+        self.map.insert(value, entity);
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        self.map.remove(entity);
+    }
+
+    fn clear(&mut self) {
+        self.map = Default::default();
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        self.map.get(val).copied()
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        Some(
+            self.map
+                .iter()
+                .flat_map(|(val, entities)| entities.map(move |entity| (*entity, val.clone()))),
+        )
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.map.len())
+    }
+
+    fn bucket_count(&self) -> Option<usize> {
+        Some(self.map.key_count())
+    }
+}
+
+impl_cached_index_storage!(HashmapStorage<I> where I::Value: Hash + Eq);
+
+impl<I: IndexInfo> HashmapStorage<I>
+where
+    I::Value: Hash + Eq,
+{
+    /// Get the value that an entity is currently indexed under, if any.
+    pub fn value_of(&self, entity: &Entity) -> Option<&I::Value> {
+        self.map.get_key(entity)
+    }
+
+    /// Get all of the entities indexed under a value, using a borrowed form of the value
+    /// (e.g. `&str` for `String` values).
+    pub fn get<'a, Q>(&'a self, val: &Q) -> impl Iterator<Item = Entity> + 'a
+    where
+        I::Value: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'a,
+    {
+        self.map.get(val).copied()
+    }
+
+    /// Iterate over every value in the index along with the entities indexed under it.
+    pub fn iter(&self) -> impl Iterator<Item = (&I::Value, impl Iterator<Item = Entity> + '_)> {
+        self.map
+            .iter()
+            .map(|(val, entities)| (val, entities.copied()))
+    }
+}
+
+impl<I: IndexInfo> MapEntities for HashmapStorage<I>
+where
+    I::Value: Hash + Eq,
+{
+    fn map_entities<E: EntityMapper>(&mut self, entity_mapper: &mut E) {
+        let old_map = std::mem::take(&mut self.map);
+        for (val, entities) in old_map.iter() {
+            for entity in entities {
+                self.map.insert(val, entity_mapper.get_mapped(*entity));
+            }
+        }
+        self.cache.map_entities(entity_mapper);
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<I: IndexInfo> HashmapStorage<I>
+where
+    I::Value: Hash + Eq,
+{
+    /// Get a [`Serialize`]able view of this storage with its values sorted, so that snapshots of
+    /// the same contents are identical regardless of the order that entities were indexed in.
+    pub fn sorted_snapshot(&self) -> SortedSnapshot<'_, I>
+    where
+        I::Value: Ord,
+    {
+        SortedSnapshot(self)
+    }
+
+    // Entities are always sorted, since that doesn't place any requirements on the values.
+    fn snapshot_entries(&self) -> Vec<(&I::Value, Vec<Entity>)> {
+        self.map
+            .iter()
+            .map(|(val, entities)| {
+                let mut entities: Vec<Entity> = entities.copied().collect();
+                entities.sort();
+                (val, entities)
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<I: IndexInfo> Serialize for HashmapStorage<I>
+where
+    I::Value: Hash + Eq + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.snapshot_entries())
+    }
+}
+
+/// A [`HashmapStorage`] that is serialized with its values in sorted order, returned by
+/// [`HashmapStorage::sorted_snapshot`].
+///
+/// It can be deserialized as a [`HashmapStorage`].
+#[cfg(feature = "serde")]
+pub struct SortedSnapshot<'a, I: IndexInfo>(&'a HashmapStorage<I>);
+
+#[cfg(feature = "serde")]
+impl<I: IndexInfo> Serialize for SortedSnapshot<'_, I>
+where
+    I::Value: Hash + Eq + Ord + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries = self.0.snapshot_entries();
+        entries.sort_by_key(|(val, _)| *val);
+        serializer.collect_seq(entries)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, I: IndexInfo> Deserialize<'de> for HashmapStorage<I>
+where
+    I::Value: Hash + Eq + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut storage = Self::default();
+        for (val, entities) in Vec::<(I::Value, Vec<Entity>)>::deserialize(deserializer)? {
+            for entity in entities {
+                storage.map.insert(&val, entity);
+            }
+        }
+        // The snapshot doesn't know which changes the world has seen since it was taken, so it is
+        // used as is until it is next refreshed, and then rebuilt from the world.
+        storage.cache.invalidate();
+        Ok(storage)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+    use crate::index::Index;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::test_util::sorted;
+    use bevy::ecs::entity::EntityHashMap;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    struct Number(u32);
+
+    impl IndexInfo for Number {
+        type Component = Number;
+        type Value = u32;
+        type Storage = HashmapStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Number, _: &()) -> u32 {
+            c.0
+        }
+    }
+
+    #[test]
+    fn test_serde_snapshot() {
+        let mut world = World::new();
+        let a = world.spawn(Number(1)).id();
+        let b = world.spawn(Number(1)).id();
+        let c = world.spawn(Number(2)).id();
+        world
+            .run_system_once(|mut idx: Index<Number>| idx.refresh())
+            .unwrap();
+
+        let json =
+            serde_json::to_string(&world.resource::<HashmapStorage<Number>>().sorted_snapshot())
+                .unwrap();
+        let mut storage: HashmapStorage<Number> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            serde_json::to_string(&storage.sorted_snapshot()).unwrap(),
+            json
+        );
+
+        // Remap the entities as if the snapshot was loaded into a different world.
+        let mut loaded = World::new();
+        loaded.spawn_empty();
+        let mut entity_map = EntityHashMap::default();
+        for entity in [a, b, c] {
+            entity_map.insert(entity, loaded.spawn_empty().id());
+        }
+        storage.map_entities(&mut entity_map);
+
+        let mut ones: Vec<_> = storage.get(&1).collect();
+        ones.sort();
+        assert_eq!(ones, vec![entity_map[&a], entity_map[&b]]);
+        assert_eq!(storage.get(&2).collect::<Vec<_>>(), vec![entity_map[&c]]);
+        assert_eq!(storage.value_of(&entity_map[&c]), Some(&2));
+    }
+
+    #[test]
+    fn test_serde_snapshot_is_deterministic() {
+        let snapshot = |values: Vec<u32>| {
+            let mut world = World::new();
+            let entities: Vec<Entity> = (0..values.len())
+                .map(|_| world.spawn_empty().id())
+                .collect();
+            // Index the same entities and values in the given order.
+            for val in values {
+                world
+                    .entity_mut(entities[val as usize])
+                    .insert(Number(val % 10));
+                world
+                    .run_system_once(|mut idx: Index<Number>| idx.refresh())
+                    .unwrap();
+            }
+            serde_json::to_string(&world.resource::<HashmapStorage<Number>>().sorted_snapshot())
+                .unwrap()
+        };
+
+        let json = snapshot((0..100).collect());
+        assert_eq!(snapshot((0..100).rev().collect()), json);
+
+        let values: Vec<u32> = serde_json::from_str::<Vec<(u32, Vec<Entity>)>>(&json)
+            .unwrap()
+            .into_iter()
+            .map(|(val, _)| val)
+            .collect();
+        assert_eq!(values, (0..10).collect::<Vec<_>>());
+    }
+
+    // Values only need to be `Hash` and `Eq` to be serialized without sorting.
+    #[derive(Component, Serialize, Deserialize, Hash, PartialEq, Eq, Clone, Copy, Debug)]
+    enum Element {
+        Fire,
+        Water,
+    }
+
+    impl IndexInfo for Element {
+        type Component = Element;
+        type Value = Element;
+        type Storage = HashmapStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::Manual;
+
+        fn value(c: &Element, _: &()) -> Element {
+            *c
+        }
+    }
+
+    #[test]
+    fn test_serde_snapshot_rebuilt_on_refresh() {
+        let mut world = World::new();
+        let a = world.spawn(Element::Fire).id();
+        let b = world.spawn(Element::Water).id();
+        world
+            .run_system_once(|mut idx: Index<Element>| idx.refresh())
+            .unwrap();
+        let json = serde_json::to_string(world.resource::<HashmapStorage<Element>>()).unwrap();
+
+        // The world changes after the snapshot is taken.
+        world.entity_mut(a).insert(Element::Water);
+        world.despawn(b);
+        let c = world.spawn(Element::Fire).id();
+
+        let loaded: HashmapStorage<Element> = serde_json::from_str(&json).unwrap();
+        world.insert_resource(loaded);
+        let lookup = |world: &mut World, element| {
+            world
+                .run_system_once(move |mut idx: Index<Element>| sorted(idx.lookup(&element)))
+                .unwrap()
+        };
+
+        // The snapshot is used as is until the index is refreshed.
+        assert_eq!(lookup(&mut world, Element::Fire), vec![a]);
+        assert_eq!(lookup(&mut world, Element::Water), vec![b]);
+
+        world
+            .run_system_once(|mut idx: Index<Element>| idx.refresh())
+            .unwrap();
+        assert_eq!(lookup(&mut world, Element::Fire), vec![c]);
+        assert_eq!(lookup(&mut world, Element::Water), vec![a]);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use std::cmp::Ordering;
+use std::ops::Range;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// Values that represent a half-open interval `[start, end)`, for use with [`IntervalStorage`].
+///
+/// Intervals whose end is not after their start are empty and never match any queries.
+pub trait IntervalBounds {
+    /// The type of the interval's endpoints.
+    type Bound: PartialOrd + Copy;
+
+    /// The first point that is contained in the interval.
+    fn start(&self) -> Self::Bound;
+
+    /// The first point after the start that is not contained in the interval.
+    fn end(&self) -> Self::Bound;
+}
+
+impl<T: PartialOrd + Copy> IntervalBounds for Range<T> {
+    type Bound = T;
+
+    fn start(&self) -> T {
+        self.start
+    }
+
+    fn end(&self) -> T {
+        self.end
+    }
+}
+
+/// [`IndexStorage`] implementation for values that are intervals, such as [`Range`]s, that
+/// allows looking up the entities whose intervals contain a point or overlap another interval.
+///
+/// Intervals are kept in an interval tree, which is rebuilt the next time it is queried after
+/// any entity's value changes. Exact lookups of a value are only efficient while the tree is
+/// up to date.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct IntervalStorage<I: IndexInfo> {
+    values: HashMap<Entity, I::Value>,
+    // Non-empty intervals sorted by their start point, treated as an implicit balanced binary tree
+    // where the middle element of each range is the root of that range, as in a binary search.
+    tree: Vec<(I::Value, Entity)>,
+    // For each node of `tree`, the position of the interval with the latest end in its subtree.
+    max_end: Vec<usize>,
+    dirty: bool,
+    cache: CacheState,
+}
+
+impl<I: IndexInfo> Default for IntervalStorage<I> {
+    fn default() -> Self {
+        Self {
+            values: Default::default(),
+            tree: Vec::new(),
+            max_end: Vec::new(),
+            dirty: false,
+            cache: Default::default(),
+        }
+    }
+}
+
+impl<I: IndexInfo> CachedStorage<I> for IntervalStorage<I>
+where
+    I::Value: IntervalBounds,
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, _component: &I::Component) {
+        if self.values.get(&entity) != Some(value) {
+            self.values.insert(entity, value.clone());
+            self.dirty = true;
+        }
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        if self.values.remove(entity).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.dirty = true;
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        // Empty intervals are left out of the tree.
+        let matches: Vec<Entity> = if self.dirty || !is_non_empty(val) {
+            self.values
+                .iter()
+                .filter(|(_, v)| *v == val)
+                .map(|(e, _)| *e)
+                .collect()
+        } else {
+            let first = self.tree.partition_point(|(v, _)| v.start() < val.start());
+            self.tree[first..]
+                .iter()
+                .take_while(|(v, _)| v.start() <= val.start())
+                .filter(|(v, _)| v == val)
+                .map(|(_, e)| *e)
+                .collect()
+        };
+        matches.into_iter()
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        Some(
+            self.values
+                .iter()
+                .map(|(entity, val)| (*entity, val.clone())),
+        )
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+impl_cached_index_storage!(IntervalStorage<I> where I::Value: IntervalBounds);
+
+impl<I: IndexInfo> IntervalStorage<I>
+where
+    I::Value: IntervalBounds,
+{
+    /// Get the value that an entity is currently indexed under, if any.
+    pub fn value_of(&self, entity: &Entity) -> Option<&I::Value> {
+        self.values.get(entity)
+    }
+
+    /// Get all of the entities whose intervals contain the given point.
+    pub fn lookup_containing(
+        &mut self,
+        point: <I::Value as IntervalBounds>::Bound,
+    ) -> impl Iterator<Item = Entity> {
+        self.search(point, |start| start <= point)
+    }
+
+    /// Get all of the entities whose intervals overlap the interval `[range.start, range.end)`.
+    pub fn lookup_overlapping(
+        &mut self,
+        range: Range<<I::Value as IntervalBounds>::Bound>,
+    ) -> impl Iterator<Item = Entity> {
+        if range.start < range.end {
+            self.search(range.start, |start| start < range.end)
+        } else {
+            Vec::new().into_iter()
+        }
+    }
+
+    // Finds the non-empty intervals that end after `after` and whose start satisfies `starts_before`.
+    fn search(
+        &mut self,
+        after: <I::Value as IntervalBounds>::Bound,
+        starts_before: impl Fn(<I::Value as IntervalBounds>::Bound) -> bool,
+    ) -> std::vec::IntoIter<Entity> {
+        if self.dirty {
+            self.rebuild_tree();
+        }
+        let mut matches = Vec::new();
+        let mut stack = vec![(0, self.tree.len())];
+        while let Some((lo, hi)) = stack.pop() {
+            if lo >= hi {
+                continue;
+            }
+            let mid = lo + (hi - lo) / 2;
+            // Nothing in this subtree ends late enough.
+            if self.tree[self.max_end[mid]].0.end() <= after {
+                continue;
+            }
+            stack.push((lo, mid));
+            let (interval, entity) = &self.tree[mid];
+            // Intervals to the right start even later.
+            if starts_before(interval.start()) {
+                if interval.end() > after {
+                    matches.push(*entity);
+                }
+                stack.push((mid + 1, hi));
+            }
+        }
+        matches.into_iter()
+    }
+
+    fn rebuild_tree(&mut self) {
+        self.tree.clear();
+        // Empty intervals never match any queries, and leaving them out also leaves out intervals
+        // with NaN bounds, which would otherwise make the sort order inconsistent.
+        self.tree.extend(
+            self.values
+                .iter()
+                .filter(|(_, v)| is_non_empty(*v))
+                .map(|(e, v)| (v.clone(), *e)),
+        );
+        self.tree
+            .sort_by(|(a, _), (b, _)| a.start().partial_cmp(&b.start()).unwrap_or(Ordering::Equal));
+        self.max_end.clear();
+        self.max_end.extend(0..self.tree.len());
+        self.build_max_end(0, self.tree.len());
+        self.dirty = false;
+    }
+
+    // Fills in `max_end` for the subtree between `lo` and `hi`, returning the root's value.
+    fn build_max_end(&mut self, lo: usize, hi: usize) -> Option<usize> {
+        if lo >= hi {
+            return None;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let mut max = mid;
+        for child in [self.build_max_end(lo, mid), self.build_max_end(mid + 1, hi)]
+            .into_iter()
+            .flatten()
+        {
+            if self.tree[child].0.end() > self.tree[max].0.end() {
+                max = child;
+            }
+        }
+        self.max_end[mid] = max;
+        Some(max)
+    }
+}
+
+fn is_non_empty(interval: &impl IntervalBounds) -> bool {
+    interval.start() < interval.end()
+}
+
+impl<'w, 's, I: IndexInfo<Storage = IntervalStorage<I>>> Index<'w, 's, I>
+where
+    I::Value: IntervalBounds,
+{
+    /// Get all of the entities whose intervals contain the given point.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn lookup_containing(
+        &mut self,
+        point: <I::Value as IntervalBounds>::Bound,
+    ) -> impl Iterator<Item = Entity> {
+        self.prepare_storage();
+        self.storage.lookup_containing(point)
+    }
+
+    /// Get all of the entities whose intervals overlap the interval `[range.start, range.end)`.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn lookup_overlapping(
+        &mut self,
+        range: Range<<I::Value as IntervalBounds>::Bound>,
+    ) -> impl Iterator<Item = Entity> {
+        self.prepare_storage();
+        self.storage.lookup_overlapping(range)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::test_util::{lookup_sorted, sorted};
+
+    #[derive(Component)]
+    struct Buff {
+        start: f32,
+        duration: f32,
+    }
+
+    struct BuffIndex;
+    impl IndexInfo for BuffIndex {
+        type Component = Buff;
+        type Value = Range<f32>;
+        type Storage = IntervalStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Buff, _: &()) -> Range<f32> {
+            c.start..c.start + c.duration
+        }
+    }
+
+    fn buff(start: f32, duration: f32) -> Buff {
+        Buff { start, duration }
+    }
+
+    fn containing(world: &mut World, t: f32) -> Vec<Entity> {
+        lookup_sorted::<BuffIndex>(world, move |idx| idx.lookup_containing(t).collect())
+    }
+
+    fn overlapping(world: &mut World, range: Range<f32>) -> Vec<Entity> {
+        lookup_sorted::<BuffIndex>(world, move |idx| {
+            idx.lookup_overlapping(range.clone()).collect()
+        })
+    }
+
+    #[test]
+    fn test_interval_storage() {
+        let mut world = World::new();
+        let a = world.spawn(buff(0., 10.)).id();
+        let b = world.spawn(buff(5., 2.)).id();
+        let c = world.spawn(buff(8., 4.)).id();
+        let empty = world.spawn(buff(6., 0.)).id();
+
+        assert_eq!(containing(&mut world, 0.), vec![a]);
+        assert_eq!(containing(&mut world, 6.), vec![a, b]);
+        assert_eq!(containing(&mut world, 7.), vec![a]);
+        assert_eq!(containing(&mut world, 10.), vec![c]);
+        assert_eq!(containing(&mut world, 12.), vec![]);
+
+        assert_eq!(overlapping(&mut world, 7.0..8.0), vec![a]);
+        assert_eq!(overlapping(&mut world, 6.5..8.5), vec![a, b, c]);
+        assert_eq!(overlapping(&mut world, 12.0..20.0), vec![]);
+        assert_eq!(overlapping(&mut world, 6.0..6.0), vec![]);
+
+        let exact = lookup_sorted::<BuffIndex>(&mut world, |idx| idx.lookup(&(6.0..6.0)).collect());
+        assert_eq!(exact, vec![empty]);
+
+        world.get_mut::<Buff>(b).unwrap().start = 11.;
+        world.despawn(a);
+        assert_eq!(containing(&mut world, 6.), vec![]);
+        assert_eq!(containing(&mut world, 11.5), vec![b, c]);
+    }
+
+    #[test]
+    fn test_interval_storage_many() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..100)
+            .map(|i| world.spawn(buff(i as f32, (i % 7) as f32)).id())
+            .collect();
+
+        for t in [0., 3.5, 50., 98.5, 200.] {
+            let expected: Vec<Entity> = sorted(
+                entities
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| (*i as f32) <= t && t < (*i as f32) + (i % 7) as f32)
+                    .map(|(_, e)| *e),
+            );
+            assert_eq!(containing(&mut world, t), expected);
+        }
+    }
+
+    #[test]
+    fn test_interval_storage_nan() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..40)
+            .map(|i| match i % 4 {
+                0 => world.spawn(buff(f32::NAN, 5.)).id(),
+                1 => world.spawn(buff(i as f32, f32::NAN)).id(),
+                _ => world.spawn(buff(i as f32, 3.)).id(),
+            })
+            .collect();
+
+        let expected = sorted(
+            entities
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| i % 4 > 1 && (*i as f32) <= 20. && 20. < *i as f32 + 3.)
+                .map(|(_, e)| *e),
+        );
+        assert_eq!(containing(&mut world, 20.), expected);
+        assert_eq!(containing(&mut world, f32::NAN), vec![]);
+        assert_eq!(overlapping(&mut world, f32::NAN..30.), vec![]);
+    }
+}
@@ -0,0 +1,144 @@
+//! # Reading storages directly
+//!
+//! Besides [`IndexStorage::lookup`][crate::storage::IndexStorage::lookup], storages have their
+//! own methods for other kinds of queries, such as
+//! [`SortedStorage::ascending`][crate::storage::SortedStorage::ascending] or
+//! [`HashmapStorage::get`][crate::storage::HashmapStorage::get]. These read the storage as it is
+//! and never refresh it. Where [`Index`][crate::index::Index] has a method of the same name, it
+//! refreshes the index according to its
+//! [`REFRESH_POLICY`][crate::index::IndexInfo::REFRESH_POLICY] first, so prefer it unless the
+//! storage is known to be up to date.
+
+use crate::index::IndexInfo;
+use bevy::ecs::system::{StaticSystemParam, SystemParam};
+use bevy::prelude::*;
+use std::time::Duration;
+
+mod aggregate;
+mod bktree;
+mod bvh;
+mod cached;
+mod composite;
+mod dense;
+mod flag;
+mod hashmap;
+mod interval;
+mod multi;
+mod no_storage;
+mod sorted;
+#[cfg(test)]
+pub(crate) mod test_util;
+mod view;
+
+pub use aggregate::*;
+pub use bktree::*;
+pub use bvh::*;
+pub use cached::*;
+pub use composite::*;
+pub use dense::*;
+pub use flag::*;
+pub use hashmap::*;
+pub use interval::*;
+pub use multi::*;
+pub use no_storage::*;
+pub use sorted::*;
+pub use view::*;
+
+/// Defines the internal storage for an index, which is stored as a [`Resource`].
+///
+/// You should not need this for normal use beyond including the `Storage` type
+/// in your [`IndexInfo`] implementations, but you can use this to customize
+/// the storage of your index's data if necessary
+///
+/// Other than [`lookup`][IndexStorage::lookup], the query methods of storages never refresh them.
+/// See [reading storages directly][crate::storage#reading-storages-directly].
+///
+/// This crate provides the following storage implementations:
+///
+/// [`HashmapStorage`], [`DenseStorage`], [`FlagStorage`], [`SortedStorage`], [`CompositeStorage`], [`BkTreeStorage`], [`IntervalStorage`], [`BvhStorage`], [`AggregateStorage`], [`ViewStorage`], [`MultiStorage`], [`NoStorage`]
+pub trait IndexStorage<I: IndexInfo>: Resource + Default {
+    /// [`SystemParam`] that is fetched alongside this storage [`Resource`] when
+    /// an [`Index`][crate::index::Index] is included in a system.
+    ///
+    /// It is passed in when querying or updating the index.
+    type RefreshData<'w, 's>: SystemParam;
+
+    /// Get all of the entities with relevant components that evaluate to the given value
+    /// using [`I::value`][`IndexInfo::value`].
+    fn lookup<'w, 's>(
+        &mut self,
+        val: &I::Value,
+        data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>,
+    ) -> impl Iterator<Item = Entity>;
+
+    /// Refresh this storage with the latest state from the world if it hasn't already been refreshed
+    /// this [`Tick`][bevy::ecs::component::Tick].
+    ///
+    /// Note: 1 [`Tick`][bevy::ecs::component::Tick] = 1 system, not 1 frame.
+    fn refresh<'w, 's>(&mut self, data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>);
+
+    /// Unconditionally refresh this storage with the latest state from the world.
+    fn force_refresh<'w, 's>(&mut self, data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>);
+
+    /// Bring this storage up to date before it is read, without fully refreshing it, e.g. by
+    /// rebuilding an index that is updated by observers after its [`IndexInfo::Data`] changed.
+    ///
+    /// This is called by [`lookup`][IndexStorage::lookup] and by [`Index`][crate::index::Index]
+    /// methods that read from the storage directly. Storages that are always up to date can use
+    /// the default implementation, which does nothing.
+    fn prepare_lookup<'w, 's>(&mut self, _data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>) {
+    }
+
+    /// Get every entity in this storage along with the value it is indexed under, without
+    /// refreshing it, e.g. to check the storage against the world.
+    ///
+    /// Storages that can't list their contents, such as [`NoStorage`] and [`DenseStorage`], can use
+    /// the default implementation, which returns `None`.
+    fn entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        None::<std::iter::Empty<_>>
+    }
+
+    /// Observer to be run whenever a component tracked by this Index is inserted.
+    ///
+    /// No observer will be registered if this returns `None`.
+    fn insertion_observer() -> Option<Observer>;
+
+    /// Observer to be run whenever a component tracked by this Index is removed.
+    ///
+    /// No observer will be registered if this returns `None`.
+    fn removal_observer() -> Option<Observer>;
+
+    /// Observers to be run whenever an entity is disabled or re-enabled, which keep disabled
+    /// entities out of the index unless [`IndexInfo::INCLUDE_DISABLED`] is set.
+    ///
+    /// Storages that don't store any entities can use the default implementation, which registers
+    /// no observers.
+    fn disabling_observers() -> Vec<Observer> {
+        Vec::new()
+    }
+
+    /// Get statistics about this storage, e.g. for [`IndexDiagnosticsPlugin`][crate::diagnostics::IndexDiagnosticsPlugin].
+    ///
+    /// Refresh statistics are totals since the last time this was called. Storages that don't keep
+    /// track of any statistics can use the default implementation, which reports nothing.
+    fn take_stats(&mut self) -> StorageStats {
+        StorageStats::default()
+    }
+}
+
+/// Statistics about an [`IndexStorage`], returned by [`IndexStorage::take_stats`].
+///
+/// Each statistic is `None` if the storage doesn't keep track of it.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct StorageStats {
+    /// Total time spent refreshing the storage.
+    pub refresh_time: Option<Duration>,
+    /// Total number of entities whose values were recomputed while refreshing the storage.
+    pub refreshed_entities: Option<usize>,
+    /// Number of entities currently in the storage.
+    pub entities: Option<usize>,
+    /// Number of distinct values currently in the storage.
+    pub buckets: Option<usize>,
+    /// Number of removed entities that will be removed from the storage in its next refresh.
+    pub pending_removals: Option<usize>,
+}
@@ -0,0 +1,194 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::prelude::*;
+use std::marker::PhantomData;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// [`IndexStorage`] implementation that feeds two [`CachedStorage`]s from a single refresh, so
+/// that [`IndexInfo::value`] is only computed once for each changed entity.
+///
+/// This allows one index to support the queries of both storages, e.g. a [`HashmapStorage`][super::HashmapStorage]
+/// for fast lookups and a [`SortedStorage`][super::SortedStorage] for ordered iteration.
+/// [`Index::lookup`] uses the primary storage, and both storages can be accessed with
+/// [`Index::primary`] and [`Index::secondary`]. Nest another `MultiStorage` as the secondary
+/// storage to use more than two.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct MultiStorage<I: IndexInfo, P: CachedStorage<I>, S: CachedStorage<I>> {
+    primary: P,
+    secondary: S,
+    cache: CacheState,
+    #[cfg_attr(feature = "reflect", reflect(ignore))]
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: IndexInfo, P: CachedStorage<I>, S: CachedStorage<I>> Default for MultiStorage<I, P, S> {
+    fn default() -> Self {
+        Self {
+            primary: Default::default(),
+            secondary: Default::default(),
+            cache: Default::default(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo, P: CachedStorage<I>, S: CachedStorage<I>> CachedStorage<I>
+    for MultiStorage<I, P, S>
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, component: &I::Component) {
+        self.primary.insert(entity, value, component);
+        self.secondary.insert(entity, value, component);
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        self.primary.remove(entity);
+        self.secondary.remove(entity);
+    }
+
+    fn clear(&mut self) {
+        self.primary.clear();
+        self.secondary.clear();
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        self.primary.cached_lookup(val)
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        self.primary.cached_entries()
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        self.primary.entity_count()
+    }
+
+    fn bucket_count(&self) -> Option<usize> {
+        self.primary.bucket_count()
+    }
+}
+
+impl_cached_index_storage!(
+    MultiStorage<I, P, S> where P: CachedStorage<I>, S: CachedStorage<I>
+);
+
+impl<I: IndexInfo, P: CachedStorage<I>, S: CachedStorage<I>> MultiStorage<I, P, S> {
+    /// Get the primary storage, which is used for [`IndexStorage::lookup`].
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// Get the secondary storage.
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+}
+
+impl<'w, 's, I, P, S> Index<'w, 's, I>
+where
+    I: IndexInfo<Storage = MultiStorage<I, P, S>>,
+    P: CachedStorage<I>,
+    S: CachedStorage<I>,
+{
+    /// Get the primary storage, which is used for [`Index::lookup`].
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn primary(&mut self) -> &P {
+        self.prepare_storage();
+        self.storage.primary()
+    }
+
+    /// Get the secondary storage.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn secondary(&mut self) -> &S {
+        self.prepare_storage();
+        self.storage.secondary()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::{HashmapStorage, SortedStorage};
+    use bevy::ecs::system::RunSystemOnce;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Component)]
+    struct Score(u32);
+
+    static MULTI_CALLS: AtomicUsize = AtomicUsize::new(0);
+    static SINGLE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    struct ScoreIndex;
+    impl IndexInfo for ScoreIndex {
+        type Component = Score;
+        type Value = u32;
+        type Storage = MultiStorage<Self, HashmapStorage<Self>, SortedStorage<Self>>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenUsed;
+
+        fn value(c: &Score, _: &()) -> u32 {
+            MULTI_CALLS.fetch_add(1, Ordering::Relaxed);
+            c.0
+        }
+    }
+
+    // The same index with only one storage, to compare how often `value` is called.
+    struct SingleScoreIndex;
+    impl IndexInfo for SingleScoreIndex {
+        type Component = Score;
+        type Value = u32;
+        type Storage = HashmapStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenUsed;
+
+        fn value(c: &Score, _: &()) -> u32 {
+            SINGLE_CALLS.fetch_add(1, Ordering::Relaxed);
+            c.0
+        }
+    }
+
+    #[test]
+    fn test_multi_storage() {
+        let mut world = World::new();
+        let a = world.spawn(Score(3)).id();
+        let b = world.spawn(Score(1)).id();
+        let c = world.spawn(Score(2)).id();
+
+        let query = |world: &mut World| {
+            world
+                .run_system_once(
+                    move |mut idx: Index<ScoreIndex>, mut single: Index<SingleScoreIndex>| {
+                        single.refresh();
+                        let twos: Vec<_> = idx.lookup(&2).collect();
+                        let ordered: Vec<_> = idx.secondary().ascending().map(|(_, e)| e).collect();
+                        (twos, ordered, idx.primary().value_of(&b).copied())
+                    },
+                )
+                .unwrap()
+        };
+
+        assert_eq!(query(&mut world), (vec![c], vec![b, c, a], Some(1)));
+        world.get_mut::<Score>(b).unwrap().0 = 5;
+        assert_eq!(query(&mut world), (vec![c], vec![c, a, b], Some(5)));
+
+        // Feeding a second storage doesn't cause any extra calls to `value`.
+        assert_eq!(
+            MULTI_CALLS.load(Ordering::Relaxed),
+            SINGLE_CALLS.load(Ordering::Relaxed)
+        );
+    }
+}
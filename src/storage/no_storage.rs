@@ -0,0 +1,72 @@
+use crate::index::IndexInfo;
+use crate::storage::IndexStorage;
+use bevy::ecs::entity_disabling::Disabled;
+use bevy::ecs::system::{StaticSystemParam, SystemParam};
+use bevy::prelude::*;
+use std::marker::PhantomData;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// [`IndexStorage`] implementation that doesn't actually store anything.
+///
+/// Whenever it is queried, it iterates over all components like you naively would if you weren't
+/// using an index. This allows you to use the `Index` interface without actually using any extra
+/// memory.
+///
+/// This storage never needs to be refreshed, so the [`Manual`](crate::refresh_policy::IndexRefreshPolicy::Manual) refresh
+/// policy is usually the best choice for index definitions that use `NoStorage`.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+pub struct NoStorage<I: IndexInfo> {
+    #[cfg_attr(feature = "reflect", reflect(ignore))]
+    phantom: PhantomData<fn() -> I>,
+}
+impl<I: IndexInfo> Default for NoStorage<I> {
+    fn default() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo> IndexStorage<I> for NoStorage<I> {
+    type RefreshData<'w, 's> = NoStorageRefreshData<'w, 's, I>;
+
+    fn lookup<'w, 's>(
+        &mut self,
+        val: &I::Value,
+        data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>,
+    ) -> impl Iterator<Item = Entity> {
+        let data = &**data;
+        data.components.iter().filter_map(move |(e, c, disabled)| {
+            if (!disabled || I::INCLUDE_DISABLED) && I::value(c, &data.data) == *val {
+                Some(e)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn refresh<'w, 's>(&mut self, _data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>) {}
+
+    fn force_refresh<'w, 's>(&mut self, _data: &mut StaticSystemParam<Self::RefreshData<'w, 's>>) {}
+
+    fn insertion_observer() -> Option<Observer> {
+        None
+    }
+
+    fn removal_observer() -> Option<Observer> {
+        None
+    }
+}
+
+type DataParam<'w, 's, T> = StaticSystemParam<'w, 's, <T as IndexInfo>::Data>;
+
+#[doc(hidden)]
+#[derive(SystemParam)]
+pub struct NoStorageRefreshData<'w, 's, I: IndexInfo> {
+    components: Query<'w, 's, (Entity, &'static <I as IndexInfo>::Component, Has<Disabled>)>,
+    data: DataParam<'w, 's, I>,
+}
@@ -0,0 +1,422 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{CacheState, CachedStorage, IndexStorage};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// [`IndexStorage`] implementation that keeps values in sorted order, allowing entities to be
+/// iterated in order of their values or looked up by a prefix of their value.
+///
+/// Lookups of a single value are `O(log n)` rather than the `O(1)` of [`HashmapStorage`][super::HashmapStorage],
+/// so prefer that storage unless you need ordering.
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct SortedStorage<I: IndexInfo> {
+    map: BTreeMap<I::Value, HashSet<Entity>>,
+    rev_map: HashMap<Entity, I::Value>,
+    cache: CacheState,
+}
+
+impl<I: IndexInfo> Default for SortedStorage<I> {
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+            rev_map: Default::default(),
+            cache: Default::default(),
+        }
+    }
+}
+
+impl<I: IndexInfo> CachedStorage<I> for SortedStorage<I>
+where
+    I::Value: Ord,
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, _component: &I::Component) {
+        if self.rev_map.get(&entity) == Some(value) {
+            return;
+        }
+        CachedStorage::<I>::remove(self, &entity);
+        self.map.entry(value.clone()).or_default().insert(entity);
+        self.rev_map.insert(entity, value.clone());
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        let Some(old_value) = self.rev_map.remove(entity) else {
+            return;
+        };
+        let entities = self
+            .map
+            .get_mut(&old_value)
+            .expect("Storage is in an invalid state: entity is missing from its value.");
+        entities.remove(entity);
+        if entities.is_empty() {
+            self.map.remove(&old_value);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+        self.rev_map.clear();
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        self.map.get(val).into_iter().flatten().copied()
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        Some(
+            self.rev_map
+                .iter()
+                .map(|(entity, val)| (*entity, val.clone())),
+        )
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        &mut self.cache
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        Some(self.rev_map.len())
+    }
+
+    fn bucket_count(&self) -> Option<usize> {
+        Some(self.map.len())
+    }
+}
+
+impl_cached_index_storage!(SortedStorage<I> where I::Value: Ord);
+
+impl<I: IndexInfo> SortedStorage<I>
+where
+    I::Value: Ord,
+{
+    /// Get the value that an entity is currently indexed under, if any.
+    pub fn value_of(&self, entity: &Entity) -> Option<&I::Value> {
+        self.rev_map.get(entity)
+    }
+
+    /// Iterate over every value in the index in ascending order, along with the entities
+    /// indexed under it.
+    pub fn iter(&self) -> impl Iterator<Item = (&I::Value, impl Iterator<Item = Entity> + '_)> {
+        self.map
+            .iter()
+            .map(|(val, entities)| (val, entities.iter().copied()))
+    }
+
+    /// Get the entity with the smallest value, along with its value.
+    ///
+    /// If several entities share the smallest value, any one of them is returned.
+    pub fn min(&self) -> Option<(&I::Value, Entity)> {
+        self.ascending().next()
+    }
+
+    /// Get the entity with the largest value, along with its value.
+    ///
+    /// If several entities share the largest value, any one of them is returned.
+    pub fn max(&self) -> Option<(&I::Value, Entity)> {
+        self.descending().next()
+    }
+
+    /// Iterate over every entity in ascending order of their values.
+    pub fn ascending(&self) -> impl Iterator<Item = (&I::Value, Entity)> {
+        self.map
+            .iter()
+            .flat_map(|(val, entities)| entities.iter().map(move |entity| (val, *entity)))
+    }
+
+    /// Iterate over every entity in descending order of their values.
+    pub fn descending(&self) -> impl Iterator<Item = (&I::Value, Entity)> {
+        self.map
+            .iter()
+            .rev()
+            .flat_map(|(val, entities)| entities.iter().map(move |entity| (val, *entity)))
+    }
+
+    /// Get the `k` entities with the largest values, in descending order.
+    pub fn top_k(&self, k: usize) -> impl Iterator<Item = (&I::Value, Entity)> {
+        self.descending().take(k)
+    }
+
+    /// Get the `k` entities with the smallest values, in ascending order.
+    pub fn bottom_k(&self, k: usize) -> impl Iterator<Item = (&I::Value, Entity)> {
+        self.ascending().take(k)
+    }
+
+    /// Get all of the entities whose values start with the given prefix, in ascending order of
+    /// their values.
+    pub fn lookup_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = Entity> + 'a
+    where
+        I::Value: Borrow<str>,
+    {
+        self.map
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(val, _)| (*val).borrow().starts_with(prefix))
+            .flat_map(|(_, entities)| entities.iter().copied())
+    }
+}
+
+impl<'w, 's, I: IndexInfo<Storage = SortedStorage<I>>> Index<'w, 's, I>
+where
+    I::Value: Ord,
+{
+    /// Iterate over every value in the index in ascending order, along with the entities
+    /// indexed under it.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn iter_sorted(
+        &mut self,
+    ) -> impl Iterator<Item = (&I::Value, impl Iterator<Item = Entity> + '_)> {
+        self.prepare_storage();
+        self.storage.iter()
+    }
+
+    /// Get the entity with the smallest value, along with its value.
+    ///
+    /// If several entities share the smallest value, any one of them is returned.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn min(&mut self) -> Option<(&I::Value, Entity)> {
+        self.prepare_storage();
+        self.storage.min()
+    }
+
+    /// Get the entity with the largest value, along with its value.
+    ///
+    /// If several entities share the largest value, any one of them is returned.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn max(&mut self) -> Option<(&I::Value, Entity)> {
+        self.prepare_storage();
+        self.storage.max()
+    }
+
+    /// Iterate over every entity in ascending order of their values.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn ascending(&mut self) -> impl Iterator<Item = (&I::Value, Entity)> {
+        self.prepare_storage();
+        self.storage.ascending()
+    }
+
+    /// Iterate over every entity in descending order of their values, like repeatedly popping
+    /// from a max-heap.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn descending(&mut self) -> impl Iterator<Item = (&I::Value, Entity)> {
+        self.prepare_storage();
+        self.storage.descending()
+    }
+
+    /// Get the `k` entities with the largest values, in descending order.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn top_k(&mut self, k: usize) -> impl Iterator<Item = (&I::Value, Entity)> {
+        self.prepare_storage();
+        self.storage.top_k(k)
+    }
+
+    /// Get the `k` entities with the smallest values, in ascending order.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn bottom_k(&mut self, k: usize) -> impl Iterator<Item = (&I::Value, Entity)> {
+        self.prepare_storage();
+        self.storage.bottom_k(k)
+    }
+
+    /// Get all of the entities whose values start with the given prefix, in ascending order of
+    /// their values.
+    ///
+    /// Refreshes the index if it has not yet been refreshed in this system and the index's
+    /// [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`] is [`WhenUsed`][crate::refresh_policy::IndexRefreshPolicy::WhenUsed].
+    pub fn lookup_prefix<'a>(&'a mut self, prefix: &'a str) -> impl Iterator<Item = Entity> + 'a
+    where
+        I::Value: Borrow<str>,
+    {
+        self.prepare_storage();
+        self.storage.lookup_prefix(prefix)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    struct Command(&'static str);
+
+    struct CommandIndex;
+    impl IndexInfo for CommandIndex {
+        type Component = Command;
+        type Value = String;
+        type Storage = SortedStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenInserted;
+
+        fn value(c: &Command, _: &()) -> String {
+            c.0.to_owned()
+        }
+    }
+
+    fn prefix(world: &mut World, prefix: &'static str) -> Vec<Entity> {
+        world
+            .run_system_once(move |mut idx: Index<CommandIndex>| {
+                idx.lookup_prefix(prefix).collect()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sorted_storage() {
+        let mut world = World::new();
+        let spawn = world.spawn(Command("spawn")).id();
+        let speed = world.spawn(Command("speed")).id();
+        let quit = world.spawn(Command("quit")).id();
+        let spawn2 = world.spawn(Command("spawn")).id();
+
+        assert_eq!(prefix(&mut world, "sp").len(), 3);
+        assert_eq!(prefix(&mut world, "spe"), vec![speed]);
+        assert_eq!(prefix(&mut world, "x"), vec![]);
+
+        let order = world
+            .run_system_once(|mut idx: Index<CommandIndex>| {
+                idx.iter_sorted()
+                    .map(|(val, entities)| (val.clone(), entities.count()))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap();
+        assert_eq!(
+            order,
+            vec![
+                ("quit".to_string(), 1),
+                ("spawn".to_string(), 2),
+                ("speed".to_string(), 1)
+            ]
+        );
+
+        world.entity_mut(spawn).insert(Command("query"));
+        world.despawn(spawn2);
+        assert_eq!(prefix(&mut world, "sp"), vec![speed]);
+        let mut q = prefix(&mut world, "q");
+        q.sort();
+        let mut expected = vec![spawn, quit];
+        expected.sort();
+        assert_eq!(q, expected);
+    }
+
+    #[derive(Component)]
+    struct Threat(u32);
+
+    struct ThreatIndex;
+    impl IndexInfo for ThreatIndex {
+        type Component = Threat;
+        type Value = u32;
+        type Storage = SortedStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Threat, _: &()) -> u32 {
+            c.0
+        }
+    }
+
+    #[test]
+    fn test_sorted_storage_extremes() {
+        let mut world = World::new();
+        let low = world.spawn(Threat(1)).id();
+        let mid = world.spawn(Threat(5)).id();
+        let high = world.spawn(Threat(9)).id();
+
+        let extremes = |world: &mut World| {
+            world
+                .run_system_once(|mut idx: Index<ThreatIndex>| {
+                    (
+                        idx.min().map(|(_, e)| e),
+                        idx.max().map(|(_, e)| e),
+                        idx.top_k(2).map(|(v, e)| (*v, e)).collect::<Vec<_>>(),
+                        idx.bottom_k(2).map(|(v, e)| (*v, e)).collect::<Vec<_>>(),
+                    )
+                })
+                .unwrap()
+        };
+        assert_eq!(
+            extremes(&mut world),
+            (
+                Some(low),
+                Some(high),
+                vec![(9, high), (5, mid)],
+                vec![(1, low), (5, mid)]
+            )
+        );
+
+        world.get_mut::<Threat>(low).unwrap().0 = 10;
+        world.despawn(high);
+        assert_eq!(
+            extremes(&mut world),
+            (
+                Some(mid),
+                Some(low),
+                vec![(10, low), (5, mid)],
+                vec![(5, mid), (10, low)]
+            )
+        );
+
+        let order = world
+            .run_system_once(|mut idx: Index<ThreatIndex>| {
+                idx.descending().map(|(_, e)| e).collect::<Vec<_>>()
+            })
+            .unwrap();
+        assert_eq!(order, vec![low, mid]);
+    }
+
+    #[derive(Resource)]
+    struct ThreatBonus(u32);
+
+    struct BonusThreatIndex;
+    impl IndexInfo for BonusThreatIndex {
+        type Component = Threat;
+        type Value = u32;
+        type Storage = SortedStorage<Self>;
+        type Data = Res<'static, ThreatBonus>;
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenInserted;
+
+        fn value(c: &Threat, bonus: &Res<ThreatBonus>) -> u32 {
+            c.0 + bonus.0
+        }
+    }
+
+    #[test]
+    fn test_sorted_storage_data_change_with_observers() {
+        let mut world = World::new();
+        world.insert_resource(ThreatBonus(0));
+        world.spawn(Threat(1));
+        world.spawn(Threat(5));
+
+        let max = |world: &mut World| {
+            world
+                .run_system_once(|mut idx: Index<BonusThreatIndex>| idx.max().map(|(v, _)| *v))
+                .unwrap()
+        };
+        assert_eq!(max(&mut world), Some(5));
+
+        // Observers don't see resource changes, so the index is rebuilt before it is read.
+        world.resource_mut::<ThreatBonus>().0 = 10;
+        assert_eq!(max(&mut world), Some(15));
+    }
+}
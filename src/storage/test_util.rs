@@ -0,0 +1,24 @@
+use crate::index::{Index, IndexInfo};
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+
+// Storages return entities in no particular order, so tests compare them sorted.
+pub(crate) fn sorted(entities: impl Iterator<Item = Entity>) -> Vec<Entity> {
+    let mut entities: Vec<_> = entities.collect();
+    entities.sort();
+    entities
+}
+
+// Runs a lookup on an index in a one-shot system, returning the entities it found in sorted order.
+pub(crate) fn lookup_sorted<I: IndexInfo>(
+    world: &mut World,
+    lookup: impl Fn(&mut Index<I>) -> Vec<Entity> + Send + Sync + 'static,
+) -> Vec<Entity> {
+    world
+        .run_system_once(move |mut idx: Index<I>| {
+            let mut entities = lookup(&mut idx);
+            entities.sort();
+            entities
+        })
+        .unwrap()
+}
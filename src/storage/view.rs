@@ -0,0 +1,202 @@
+use crate::index::IndexInfo;
+use crate::storage::cached::impl_cached_index_storage;
+use crate::storage::{
+    Aggregate,
+    AggregateStorage,
+    AggregatingStorage,
+    CacheState,
+    CachedStorage,
+    IndexStorage,
+};
+use bevy::ecs::system::StaticSystemParam;
+use bevy::prelude::*;
+use std::hash::Hash;
+
+#[cfg(feature = "reflect")]
+use bevy::reflect::Reflect;
+
+/// [`IndexStorage`] implementation that maintains a materialized view of each value: the entities
+/// with that value, along with an [`Aggregate`] of their components, such as the centroid of all
+/// units in a squad.
+///
+/// The aggregate for each value is updated incrementally as entities gain and lose the value, and
+/// can be read with [`Index::aggregate`][crate::index::Index::aggregate]. This is an
+/// [`AggregateStorage`] that also keeps the entities with each value, so lookups don't have to
+/// check every entity. Requires the index's values to implement [`Hash`] and [`Eq`].
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+#[cfg_attr(feature = "reflect", reflect(Resource))]
+#[derive(Resource)]
+pub struct ViewStorage<I: IndexInfo, A: Aggregate<I::Component>> {
+    aggregate: AggregateStorage<I, A>,
+}
+
+impl<I: IndexInfo, A: Aggregate<I::Component>> Default for ViewStorage<I, A>
+where
+    I::Value: Hash + Eq,
+{
+    fn default() -> Self {
+        Self {
+            aggregate: AggregateStorage::tracking_entities(),
+        }
+    }
+}
+
+impl<I: IndexInfo, A: Aggregate<I::Component>> CachedStorage<I> for ViewStorage<I, A>
+where
+    I::Value: Hash + Eq,
+{
+    fn insert(&mut self, entity: Entity, value: &I::Value, component: &I::Component) {
+        self.aggregate.insert(entity, value, component);
+    }
+
+    fn remove(&mut self, entity: &Entity) {
+        CachedStorage::<I>::remove(&mut self.aggregate, entity);
+    }
+
+    fn clear(&mut self) {
+        CachedStorage::<I>::clear(&mut self.aggregate);
+    }
+
+    fn cached_lookup(&self, val: &I::Value) -> impl Iterator<Item = Entity> {
+        self.aggregate.cached_lookup(val)
+    }
+
+    fn cached_entries(&self) -> Option<impl Iterator<Item = (Entity, I::Value)>> {
+        self.aggregate.cached_entries()
+    }
+
+    fn cache_state(&mut self) -> &mut CacheState {
+        CachedStorage::<I>::cache_state(&mut self.aggregate)
+    }
+
+    fn entity_count(&self) -> Option<usize> {
+        CachedStorage::<I>::entity_count(&self.aggregate)
+    }
+
+    fn bucket_count(&self) -> Option<usize> {
+        CachedStorage::<I>::bucket_count(&self.aggregate)
+    }
+}
+
+impl_cached_index_storage!(
+    ViewStorage<I, A> where I::Value: Hash + Eq, A: Aggregate<I::Component>
+);
+
+impl<I: IndexInfo, A: Aggregate<I::Component>> AggregatingStorage<I> for ViewStorage<I, A>
+where
+    I::Value: Hash + Eq,
+{
+    type Aggregate = A;
+
+    fn count(&self, val: &I::Value) -> usize {
+        self.aggregate.count(val)
+    }
+
+    fn aggregate(&self, val: &I::Value) -> Option<&A::Output> {
+        self.aggregate.aggregate(val)
+    }
+}
+
+impl<I: IndexInfo, A: Aggregate<I::Component>> ViewStorage<I, A>
+where
+    I::Value: Hash + Eq,
+{
+    /// Get the value that an entity is currently indexed under, if any.
+    pub fn value_of(&self, entity: &Entity) -> Option<&I::Value> {
+        self.aggregate.value_of(entity)
+    }
+
+    /// Iterate over every value in the index along with its entities and aggregate.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&I::Value, impl Iterator<Item = Entity> + '_, &A::Output)> {
+        self.aggregate.iter_entities()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::Index;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    struct Unit {
+        squad: u32,
+        position: Vec2,
+    }
+
+    // Keeps the sum of positions and the number of units to compute their centroid.
+    struct Centroid;
+    impl Aggregate<Unit> for Centroid {
+        type Input = Vec2;
+        type Output = (Vec2, f32);
+
+        fn input(unit: &Unit) -> Vec2 {
+            unit.position
+        }
+
+        fn add((sum, count): &mut (Vec2, f32), position: &Vec2) {
+            *sum += *position;
+            *count += 1.;
+        }
+
+        fn remove((sum, count): &mut (Vec2, f32), position: &Vec2) {
+            *sum -= *position;
+            *count -= 1.;
+        }
+    }
+
+    struct SquadIndex;
+    impl IndexInfo for SquadIndex {
+        type Component = Unit;
+        type Value = u32;
+        type Storage = ViewStorage<Self, Centroid>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::WhenRun;
+
+        fn value(c: &Unit, _: &()) -> u32 {
+            c.squad
+        }
+    }
+
+    fn centroid(world: &mut World, squad: u32) -> Option<Vec2> {
+        world
+            .run_system_once(move |mut idx: Index<SquadIndex>| {
+                idx.aggregate(&squad).map(|(sum, count)| *sum / *count)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_view_storage() {
+        let mut world = World::new();
+        let unit = |squad, x, y| Unit {
+            squad,
+            position: Vec2::new(x, y),
+        };
+        let a = world.spawn(unit(1, 0., 0.)).id();
+        let b = world.spawn(unit(1, 2., 4.)).id();
+        world.spawn(unit(2, 10., 10.));
+
+        assert_eq!(centroid(&mut world, 1), Some(Vec2::new(1., 2.)));
+        assert_eq!(centroid(&mut world, 2), Some(Vec2::new(10., 10.)));
+        assert_eq!(centroid(&mut world, 3), None);
+
+        world.get_mut::<Unit>(b).unwrap().position = Vec2::new(4., 0.);
+        assert_eq!(centroid(&mut world, 1), Some(Vec2::new(2., 0.)));
+
+        world.get_mut::<Unit>(a).unwrap().squad = 2;
+        assert_eq!(centroid(&mut world, 1), Some(Vec2::new(4., 0.)));
+        assert_eq!(centroid(&mut world, 2), Some(Vec2::new(5., 5.)));
+
+        let (members, count) = world
+            .run_system_once(|mut idx: Index<SquadIndex>| {
+                (idx.lookup(&1).collect::<Vec<_>>(), idx.count(&2))
+            })
+            .unwrap();
+        assert_eq!(members, vec![b]);
+        assert_eq!(count, 2);
+    }
+}
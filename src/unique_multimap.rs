@@ -2,6 +2,7 @@ use bevy::platform::collections::{
     hash_map::HashMap,
     hash_set::{HashSet, Iter},
 };
+use std::borrow::Borrow;
 use std::hash::Hash;
 
 #[cfg(feature = "reflect")]
@@ -30,12 +31,36 @@ where
     K: Hash + Eq + Clone,
     V: Hash + Eq + Clone,
 {
-    pub fn get(&self, k: &K) -> impl Iterator<Item = &V> {
+    pub fn get<Q>(&self, k: &Q) -> impl Iterator<Item = &V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
         MultiMapValueIter {
             inner: self.map.get(k).map(|hashset| hashset.iter()),
         }
     }
 
+    /// Iterates over every key along with the values stored under it.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, impl Iterator<Item = &V>)> {
+        self.map.iter().map(|(k, set)| (k, set.iter()))
+    }
+
+    /// Number of values in the map.
+    pub fn len(&self) -> usize {
+        self.rev_map.len()
+    }
+
+    /// Number of keys with at least one value.
+    pub fn key_count(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns the key that the value is currently stored under.
+    pub fn get_key(&self, v: &V) -> Option<&K> {
+        self.rev_map.get(v)
+    }
+
     /// Returns value's old key
     // Todo: don't rely on clone
     pub fn insert(&mut self, new_k: &K, v: V) -> Option<K> {
@@ -0,0 +1,356 @@
+use crate::index::{Index, IndexInfo};
+use crate::storage::IndexStorage;
+use bevy::ecs::entity_disabling::Disabled;
+use bevy::ecs::system::StaticSystemParam;
+use bevy::log::warn;
+use bevy::platform::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_real_timer;
+use std::any::type_name;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// [`Event`] sent by [`validate_index_system`] for each entity whose indexed value doesn't match
+/// the value computed from its current component.
+#[derive(Event)]
+pub struct IndexMismatch<I: IndexInfo> {
+    /// The entity that was indexed incorrectly.
+    pub entity: Entity,
+    /// How the index disagrees with the world.
+    pub kind: IndexMismatchKind<I::Value>,
+}
+
+/// The ways that an index can disagree with the world.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexMismatchKind<V> {
+    /// The entity's component has this value, but looking up the value doesn't return the entity.
+    Missing(V),
+    /// Looking up `indexed` returns the entity, but its component has a different value, or `None`
+    /// if the entity no longer has the component.
+    Stale {
+        /// The value that the entity was found under.
+        indexed: V,
+        /// The value computed from the entity's current component, if any.
+        actual: Option<V>,
+    },
+}
+
+/// A [`System`] that recomputes the value of every indexed component
+/// from scratch and checks that the index agrees, logging a warning and sending an
+/// [`IndexMismatch`] event for each entity where it doesn't.
+///
+/// The index is checked as it would be seen by any other system, so indexes are refreshed first
+/// according to their [`REFRESH_POLICY`][`IndexInfo::REFRESH_POLICY`]. Stale entities are found by
+/// walking the storage's [`entries`][IndexStorage::entries]. For storages that can't list their
+/// contents, they are only found under values that some component currently has. This is
+/// expensive, so it should only be used for debugging.
+///
+/// For indexes whose values don't implement [`Hash`], use [`validate_unhashed_index_system`].
+pub fn validate_index_system<I: IndexInfo>(
+    index: Index<I>,
+    components: ValidatedComponents<I>,
+    data: StaticSystemParam<I::Data>,
+    mismatches: EventWriter<IndexMismatch<I>>,
+) where
+    I::Value: Debug + Hash + Eq,
+{
+    validate_index::<I, HashSet<I::Value>>(index, components, data, mismatches);
+}
+
+/// A version of [`validate_index_system`] for indexes whose values don't implement [`Hash`].
+///
+/// Distinct values are found by comparing every pair of values, so this takes quadratic time in the
+/// number of distinct values.
+pub fn validate_unhashed_index_system<I: IndexInfo>(
+    index: Index<I>,
+    components: ValidatedComponents<I>,
+    data: StaticSystemParam<I::Data>,
+    mismatches: EventWriter<IndexMismatch<I>>,
+) where
+    I::Value: Debug,
+{
+    validate_index::<I, Vec<I::Value>>(index, components, data, mismatches);
+}
+
+type ValidatedComponents<'w, 's, I> =
+    Query<'w, 's, (Entity, &'static <I as IndexInfo>::Component, Has<Disabled>)>;
+
+// The distinct values of the components being validated.
+trait ValueSet<V>: Default + IntoIterator<Item = V> {
+    fn insert_value(&mut self, value: &V);
+}
+
+impl<V: PartialEq + Clone> ValueSet<V> for Vec<V> {
+    fn insert_value(&mut self, value: &V) {
+        if !self.contains(value) {
+            self.push(value.clone());
+        }
+    }
+}
+
+impl<V: Hash + Eq + Clone> ValueSet<V> for HashSet<V> {
+    fn insert_value(&mut self, value: &V) {
+        if !self.contains(value) {
+            self.insert(value.clone());
+        }
+    }
+}
+
+fn validate_index<I: IndexInfo, S: ValueSet<I::Value>>(
+    mut index: Index<I>,
+    components: ValidatedComponents<I>,
+    data: StaticSystemParam<I::Data>,
+    mut mismatches: EventWriter<IndexMismatch<I>>,
+) where
+    I::Value: Debug,
+{
+    let mut report = |entity: Entity, kind: IndexMismatchKind<I::Value>| {
+        warn!(
+            "Index `{}` disagrees with the world for {entity}: {kind:?}",
+            type_name::<I>()
+        );
+        mismatches.write(IndexMismatch { entity, kind });
+    };
+
+    index.prepare_storage();
+    let mut values = S::default();
+    let mut actual_values = HashMap::new();
+    for (entity, component, disabled) in &components {
+        if disabled && !I::INCLUDE_DISABLED {
+            continue;
+        }
+        let value = I::value(component, &data);
+        if !index.lookup(&value).any(|e| e == entity) {
+            report(entity, IndexMismatchKind::Missing(value.clone()));
+        }
+        values.insert_value(&value);
+        actual_values.insert(entity, value);
+    }
+
+    let mut check_stale = |entity: Entity, indexed: I::Value| match actual_values.get(&entity) {
+        Some(actual) if *actual == indexed => {}
+        actual => report(
+            entity,
+            IndexMismatchKind::Stale {
+                indexed,
+                actual: actual.cloned(),
+            },
+        ),
+    };
+
+    if let Some(entries) = index.storage.entries() {
+        for (entity, indexed) in entries {
+            check_stale(entity, indexed);
+        }
+        return;
+    }
+
+    for value in values {
+        let found: Vec<Entity> = index.lookup(&value).collect();
+        for entity in found {
+            check_stale(entity, value.clone());
+        }
+    }
+}
+
+/// A [`Plugin`] that runs [`validate_index_system`] for an index during the [`Last`] schedule.
+///
+/// By default, the index is validated every frame. Use [`every`][Self::every] to validate it less
+/// often. To validate at a different time, or to validate an index whose values don't implement
+/// [`Hash`], add the [`IndexMismatch`] event and the system to your app yourself.
+pub struct IndexValidationPlugin<I: IndexInfo> {
+    interval: Option<Duration>,
+    phantom: PhantomData<fn() -> I>,
+}
+
+impl<I: IndexInfo> Default for IndexValidationPlugin<I> {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I: IndexInfo> IndexValidationPlugin<I> {
+    /// Validate the index at most once per `interval` of real time, rather than every frame.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            interval: Some(interval),
+            ..Default::default()
+        }
+    }
+}
+
+impl<I: IndexInfo> Plugin for IndexValidationPlugin<I>
+where
+    I::Value: Debug + Hash + Eq,
+{
+    fn build(&self, app: &mut App) {
+        app.add_event::<IndexMismatch<I>>();
+        match self.interval {
+            Some(interval) => app.add_systems(
+                Last,
+                validate_index_system::<I>.run_if(on_real_timer(interval)),
+            ),
+            None => app.add_systems(Last, validate_index_system::<I>),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::refresh_policy::IndexRefreshPolicy;
+    use crate::storage::HashmapStorage;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[derive(Component)]
+    struct Number(u32);
+
+    impl IndexInfo for Number {
+        type Component = Number;
+        type Value = u32;
+        type Storage = HashmapStorage<Self>;
+        type Data = ();
+        const REFRESH_POLICY: IndexRefreshPolicy = IndexRefreshPolicy::Manual;
+
+        fn value(c: &Number, _: &()) -> u32 {
+            c.0
+        }
+    }
+
+    fn mismatches(app: &mut App) -> Vec<(Entity, IndexMismatchKind<u32>)> {
+        app.update();
+        let events = app.world().resource::<Events<IndexMismatch<Number>>>();
+        let mut mismatches: Vec<_> = events
+            .iter_current_update_events()
+            .map(|m| (m.entity, m.kind.clone()))
+            .collect();
+        mismatches.sort_by_key(|(entity, _)| *entity);
+        mismatches
+    }
+
+    #[test]
+    fn test_validation() {
+        let mut app = App::new();
+        app.add_plugins(IndexValidationPlugin::<Number>::default());
+        let a = app.world_mut().spawn(Number(1)).id();
+        let b = app.world_mut().spawn(Number(2)).id();
+        app.world_mut()
+            .run_system_once(|mut idx: Index<Number>| idx.refresh())
+            .unwrap();
+        assert_eq!(mismatches(&mut app), vec![]);
+
+        // Changes aren't seen by `Manual` indexes until they are refreshed.
+        app.world_mut().get_mut::<Number>(a).unwrap().0 = 2;
+        app.world_mut().entity_mut(b).remove::<Number>();
+        let c = app.world_mut().spawn(Number(1)).id();
+        assert_eq!(
+            mismatches(&mut app),
+            vec![
+                (a, IndexMismatchKind::Missing(2)),
+                (
+                    a,
+                    IndexMismatchKind::Stale {
+                        indexed: 1,
+                        actual: Some(2)
+                    }
+                ),
+                (
+                    b,
+                    IndexMismatchKind::Stale {
+                        indexed: 2,
+                        actual: None
+                    }
+                ),
+                (c, IndexMismatchKind::Missing(1)),
+            ]
+        );
+
+        app.world_mut()
+            .run_system_once(|mut idx: Index<Number>| idx.refresh())
+            .unwrap();
+        assert_eq!(mismatches(&mut app), vec![]);
+    }
+
+    #[test]
+    fn test_validation_stale_value_without_holder() {
+        let mut app = App::new();
+        app.add_plugins(IndexValidationPlugin::<Number>::default());
+        let a = app.world_mut().spawn(Number(7)).id();
+        let b = app.world_mut().spawn(Number(8)).id();
+        app.world_mut()
+            .run_system_once(|mut idx: Index<Number>| idx.refresh())
+            .unwrap();
+
+        // No live entity has either value anymore, but the index still has them.
+        app.world_mut().despawn(a);
+        app.world_mut().get_mut::<Number>(b).unwrap().0 = 9;
+        assert_eq!(
+            mismatches(&mut app),
+            vec![
+                (
+                    a,
+                    IndexMismatchKind::Stale {
+                        indexed: 7,
+                        actual: None
+                    }
+                ),
+                (b, IndexMismatchKind::Missing(9)),
+                (
+                    b,
+                    IndexMismatchKind::Stale {
+                        indexed: 8,
+                        actual: Some(9)
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validation_interval() {
+        let mut app = App::new();
+        app.init_resource::<Time<Real>>()
+            .add_plugins(IndexValidationPlugin::<Number>::every(Duration::from_secs(
+                1,
+            )));
+        let a = app.world_mut().spawn(Number(1)).id();
+        // The first update only starts the clock.
+        app.world_mut()
+            .resource_mut::<Time<Real>>()
+            .update_with_duration(Duration::ZERO);
+
+        let validate_after = |app: &mut App, millis| {
+            app.world_mut()
+                .resource_mut::<Time<Real>>()
+                .update_with_duration(Duration::from_millis(millis));
+            mismatches(app)
+        };
+        assert_eq!(validate_after(&mut app, 600), vec![]);
+        assert_eq!(
+            validate_after(&mut app, 600),
+            vec![(a, IndexMismatchKind::Missing(1))]
+        );
+        assert_eq!(validate_after(&mut app, 600), vec![]);
+    }
+
+    #[test]
+    fn test_unhashed_validation() {
+        let mut app = App::new();
+        app.add_event::<IndexMismatch<Number>>()
+            .add_systems(Last, validate_unhashed_index_system::<Number>);
+        let a = app.world_mut().spawn(Number(1)).id();
+        let b = app.world_mut().spawn(Number(1)).id();
+        assert_eq!(
+            mismatches(&mut app),
+            vec![
+                (a, IndexMismatchKind::Missing(1)),
+                (b, IndexMismatchKind::Missing(1)),
+            ]
+        );
+    }
+}